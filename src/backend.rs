@@ -0,0 +1,188 @@
+mod apt;
+mod brew;
+mod cargo;
+mod custom;
+mod dnf;
+mod flatpak;
+mod git;
+mod npm;
+mod pacman;
+mod pipx;
+mod snap;
+mod url;
+mod winget;
+
+use mlua::Lua;
+use os_info::Type;
+use std::collections::HashMap;
+use std::process::Command;
+
+use crate::config::Config;
+use crate::history::History;
+use crate::package::{Package, PackageData, PackageType};
+use crate::runner::Runner;
+
+use anyhow::{anyhow, Result};
+
+/// A package manager nexus knows how to drive.
+///
+/// Each backend lives in its own module under `backend/` and is registered in
+/// [`Registry::new`] against the [`PackageType`] it handles. Backends build
+/// commands and hand them to the [`Runner`] rather than spawning them.
+pub trait Backend: Send + Sync {
+    /// Whether this backend can be used on the given operating system
+    fn is_supported(&self, runner: &dyn Runner, os: Type) -> bool;
+
+    fn install(&self, runner: &dyn Runner, pkg: &PackageData) -> Result<bool>;
+
+    fn uninstall(&self, runner: &dyn Runner, pkg: &PackageData) -> Result<bool>;
+
+    fn update(&self, runner: &dyn Runner, pkg: &PackageData) -> Result<bool>;
+
+    /// Installs `pkg` with access to the lua functions declared alongside it.
+    /// Backends may record what they resolved (e.g. a commit) in its data.
+    fn install_package(&self, runner: &dyn Runner, pkg: &mut Package) -> Result<bool> {
+        self.install(runner, &pkg.package_data)
+    }
+
+    /// Installs `pkg` over an installed copy whose definition changed. Package
+    /// managers that skip packages which are already installed override this.
+    fn reinstall(&self, runner: &dyn Runner, pkg: &PackageData) -> Result<bool> {
+        self.install(runner, pkg)
+    }
+
+    /// Reinstalls `pkg` with access to its lua functions, see [`Backend::install_package`]
+    fn reinstall_package(&self, runner: &dyn Runner, pkg: &mut Package) -> Result<bool> {
+        self.reinstall(runner, &pkg.package_data)
+    }
+
+    /// Updates `pkg` with access to its lua functions, see [`Backend::install_package`]
+    fn update_package(&self, runner: &dyn Runner, pkg: &mut Package) -> Result<bool> {
+        self.update(runner, &pkg.package_data)
+    }
+
+    /// Whether `pkg` can be installed together with other packages of its type
+    /// through [`Backend::install_batch`]
+    fn can_batch(&self, _pkg: &PackageData) -> bool {
+        false
+    }
+
+    /// Installs all of `pkgs` with a single invocation of the package manager.
+    /// Only called with packages [`Backend::can_batch`] accepted.
+    fn install_batch(&self, runner: &dyn Runner, pkgs: &[&PackageData]) -> Result<bool> {
+        for pkg in pkgs {
+            if !self.install(runner, pkg)? {
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// Whether `pkg` can be installed at exactly the version [`Backend::query`]
+    /// reports by setting it as the package's `version`
+    fn can_pin(&self, _pkg: &PackageData) -> bool {
+        false
+    }
+
+    /// Whether the package manager has to be run through sudo
+    fn requires_root(&self) -> bool {
+        false
+    }
+
+    /// Returns the currently installed version of the package, or `None` if it
+    /// is not installed or the backend has no way of telling
+    fn query(&self, _runner: &dyn Runner, _pkg: &PackageData) -> Result<Option<String>> {
+        Ok(None)
+    }
+}
+
+pub struct Registry {
+    backends: HashMap<PackageType, Box<dyn Backend>>,
+    runner: Box<dyn Runner>,
+    jobs: usize,
+    history: History,
+}
+
+impl Registry {
+    pub fn new(config: &Config, runner: Box<dyn Runner>) -> Self {
+        let mut registry = Self {
+            backends: HashMap::new(),
+            runner,
+            jobs: 1,
+            history: History::new(config),
+        };
+
+        registry.register(PackageType::Apt, Box::new(apt::Apt));
+        registry.register(PackageType::Snap, Box::new(snap::Snap));
+        registry.register(PackageType::Brew, Box::new(brew::Brew));
+        registry.register(PackageType::Winget, Box::new(winget::Winget));
+        registry.register(PackageType::Pacman, Box::new(pacman::Pacman::default()));
+        registry.register(PackageType::Dnf, Box::new(dnf::Dnf));
+        registry.register(PackageType::Flatpak, Box::new(flatpak::Flatpak));
+        registry.register(PackageType::Cargo, Box::new(cargo::Cargo));
+        registry.register(PackageType::Pipx, Box::new(pipx::Pipx));
+        registry.register(PackageType::Npm, Box::new(npm::Npm));
+        registry.register(PackageType::Url, Box::new(url::Url::new(config)));
+        registry.register(PackageType::Git, Box::new(git::Git::new(config)));
+
+        registry
+    }
+
+    /// Registers the backends defined in lua within the config's backends directory
+    pub fn load_lua_backends(&mut self, lua: &Lua, config: &Config) -> Result<(), String> {
+        for (name, backend) in custom::load(lua, &config.backends)? {
+            let package_type: PackageType =
+                serde_json::from_value(serde_json::Value::String(name.clone()))
+                    .map_err(|e| format!("Invalid backend name {}: {}", name, e))?;
+
+            if self.backends.contains_key(&package_type) {
+                eprintln!("WARNING: Lua backend {} replaces the builtin backend", name);
+            }
+
+            self.register(package_type, Box::new(backend));
+        }
+
+        Ok(())
+    }
+
+    pub fn register(&mut self, package_type: PackageType, backend: Box<dyn Backend>) {
+        self.backends.insert(package_type, backend);
+    }
+
+    pub fn runner(&self) -> &dyn Runner {
+        self.runner.as_ref()
+    }
+
+    /// Sets how many backends may install packages at the same time
+    pub fn set_jobs(&mut self, jobs: usize) {
+        self.jobs = jobs.max(1);
+    }
+
+    pub fn jobs(&self) -> usize {
+        self.jobs
+    }
+
+    /// Where operations on packages are logged
+    pub fn history(&self) -> &History {
+        &self.history
+    }
+
+    pub fn get(&self, package_type: &PackageType) -> Result<&dyn Backend> {
+        self.backends
+            .get(package_type)
+            .map(|b| b.as_ref())
+            .ok_or_else(|| anyhow!("No backend registered for {} packages", package_type))
+    }
+}
+
+/// Creates a command for `program`, escalating through sudo if the backend requires root
+fn command(backend: &dyn Backend, program: &str) -> Command {
+    if backend.requires_root() {
+        let mut cmd = Command::new("sudo");
+        cmd.arg(program);
+        cmd
+    } else {
+        Command::new(program)
+    }
+}
@@ -0,0 +1,144 @@
+use os_info::Type;
+use std::process::Command;
+
+use super::{command, Backend};
+use crate::package::PackageData;
+use crate::runner::Runner;
+
+use anyhow::Result;
+
+pub struct Apt;
+
+impl Apt {
+    /// What to pass to `apt install` for `pkg`
+    fn install_target(runner: &dyn Runner, pkg: &PackageData) -> String {
+        if pkg.channel.is_some() {
+            runner.warn(
+                "WARNING: Channels are not supported for apt packages.\nSkipping channel argument",
+            );
+        }
+
+        // A local .deb is installed by path, its version is whatever the file contains
+        if let Some(source) = &pkg.source {
+            if pkg.version.is_some() {
+                runner.warn(
+                    "WARNING: Versions are ignored for apt packages installed from a local file.\nSkipping version argument",
+                );
+            }
+
+            return source.clone();
+        }
+
+        match &pkg.version {
+            Some(version) => format!("{}={}", &pkg.name, version),
+            None => pkg.name.clone(),
+        }
+    }
+}
+
+impl Backend for Apt {
+    fn is_supported(&self, _runner: &dyn Runner, os: Type) -> bool {
+        os == Type::Pop || os == Type::Debian || os == Type::Ubuntu
+    }
+
+    fn requires_root(&self) -> bool {
+        true
+    }
+
+    fn install(&self, runner: &dyn Runner, pkg: &PackageData) -> Result<bool> {
+        self.install_batch(runner, &[pkg])
+    }
+
+    fn can_batch(&self, _pkg: &PackageData) -> bool {
+        true
+    }
+
+    fn install_batch(&self, runner: &dyn Runner, pkgs: &[&PackageData]) -> Result<bool> {
+        let mut cmd = command(self, "apt");
+        let mut args: Vec<String> = Vec::from(["install".to_string()]);
+
+        args.extend(pkgs.iter().map(|pkg| Self::install_target(runner, pkg)));
+        args.push("-y".to_string());
+
+        cmd.args(args);
+
+        runner.run(cmd)
+    }
+
+    /// A changed or restored version may be older than the installed one, which
+    /// apt refuses to install unless downgrades are allowed. A changed local
+    /// .deb may keep its version, which apt skips unless told to reinstall.
+    fn reinstall(&self, runner: &dyn Runner, pkg: &PackageData) -> Result<bool> {
+        let mut cmd = command(self, "apt");
+        let mut args: Vec<String> = Vec::from(["install".to_string()]);
+
+        if pkg.source.is_some() {
+            args.push("--reinstall".to_string());
+        }
+
+        args.extend([
+            "--allow-downgrades".to_string(),
+            Self::install_target(runner, pkg),
+            "-y".to_string(),
+        ]);
+
+        cmd.args(args);
+
+        runner.run(cmd)
+    }
+
+    fn can_pin(&self, pkg: &PackageData) -> bool {
+        // A local .deb only has the version of the file
+        pkg.source.is_none()
+    }
+
+    fn uninstall(&self, runner: &dyn Runner, pkg: &PackageData) -> Result<bool> {
+        let mut cmd = command(self, "apt");
+        let mut args: Vec<String> = Vec::from(["remove".to_string()]);
+
+        if let Some(version) = &pkg.version {
+            args.push(format!("{}={}", &pkg.name, version));
+        } else {
+            args.push(pkg.name.clone());
+        }
+
+        if pkg.channel.is_some() {
+            runner.warn(
+                "WARNING: Channels are not supported for apt packages.\nSkipping channel argument",
+            );
+        }
+
+        args.push("-y".to_string());
+
+        cmd.args(args);
+
+        runner.run(cmd)
+    }
+
+    fn update(&self, runner: &dyn Runner, pkg: &PackageData) -> Result<bool> {
+        let mut cmd = command(self, "apt");
+        let args: Vec<String> = Vec::from([
+            "install".to_string(),
+            "--only-upgrade".to_string(),
+            pkg.name.clone(),
+            "-y".to_string(),
+        ]);
+
+        if pkg.channel.is_some() {
+            runner.warn(
+                "WARNING: Channels are not supported for apt packages.\nSkipping channel argument",
+            );
+        }
+
+        cmd.args(args);
+
+        runner.run(cmd)
+    }
+
+    fn query(&self, runner: &dyn Runner, pkg: &PackageData) -> Result<Option<String>> {
+        let mut cmd = Command::new("dpkg-query");
+        cmd.args(["-W", "-f=${Version}", &pkg.name]);
+
+        runner.output(cmd)
+    }
+}
@@ -0,0 +1,78 @@
+use os_info::Type;
+use std::process::Command;
+
+use super::Backend;
+use crate::package::PackageData;
+use crate::runner::Runner;
+
+use anyhow::Result;
+
+pub struct Brew;
+
+impl Backend for Brew {
+    fn is_supported(&self, _runner: &dyn Runner, os: Type) -> bool {
+        os == Type::Macos
+    }
+
+    fn install(&self, runner: &dyn Runner, pkg: &PackageData) -> Result<bool> {
+        self.install_batch(runner, &[pkg])
+    }
+
+    fn can_batch(&self, _pkg: &PackageData) -> bool {
+        true
+    }
+
+    fn install_batch(&self, runner: &dyn Runner, pkgs: &[&PackageData]) -> Result<bool> {
+        let mut cmd = Command::new("brew");
+        let mut args: Vec<String> = Vec::from(["install".to_string()]);
+
+        for pkg in pkgs {
+            let mut version_arg: String = pkg.name.clone();
+
+            if let Some(version) = &pkg.version {
+                version_arg.push_str(format!("@{}", version).as_str());
+            }
+
+            args.push(version_arg);
+        }
+
+        cmd.args(args);
+
+        runner.run(cmd)
+    }
+
+    /// brew reports versions with their revision (e.g. `3.12.1_1`) and only
+    /// has versioned formulae for some major versions, so an installed version
+    /// can't be asked for again
+    fn can_pin(&self, _pkg: &PackageData) -> bool {
+        false
+    }
+
+    fn uninstall(&self, runner: &dyn Runner, pkg: &PackageData) -> Result<bool> {
+        let mut cmd = Command::new("brew");
+        let args: Vec<String> = Vec::from(["uninstall".to_string(), pkg.name.clone()]);
+
+        cmd.args(args);
+
+        runner.run(cmd)
+    }
+
+    fn update(&self, runner: &dyn Runner, pkg: &PackageData) -> Result<bool> {
+        let mut cmd = Command::new("brew");
+        let args: Vec<String> = Vec::from(["upgrade".to_string(), pkg.name.clone()]);
+
+        cmd.args(args);
+
+        runner.run(cmd)
+    }
+
+    fn query(&self, runner: &dyn Runner, pkg: &PackageData) -> Result<Option<String>> {
+        let mut cmd = Command::new("brew");
+        cmd.args(["list", "--versions", &pkg.name]);
+
+        // Output looks like "name 1.2.3 1.2.4", the last entry is the newest
+        Ok(runner
+            .output(cmd)?
+            .and_then(|out| out.split_whitespace().last().map(|v| v.to_string())))
+    }
+}
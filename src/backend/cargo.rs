@@ -0,0 +1,101 @@
+use os_info::Type;
+use std::process::Command;
+
+use super::Backend;
+use crate::package::PackageData;
+use crate::runner::Runner;
+
+use anyhow::Result;
+
+pub struct Cargo;
+
+impl Cargo {
+    fn install_args(runner: &dyn Runner, pkg: &PackageData) -> Vec<String> {
+        let mut args: Vec<String> = Vec::from(["install".to_string()]);
+
+        if let Some(git) = &pkg.git {
+            args.push("--git".to_string());
+            args.push(git.clone());
+        } else if let Some(path) = &pkg.path {
+            args.push("--path".to_string());
+            args.push(path.clone());
+        }
+
+        // A path install builds whatever crate lives there, so the name is only passed otherwise
+        if pkg.path.is_none() || pkg.git.is_some() {
+            args.push(pkg.name.clone());
+        }
+
+        if let Some(version) = &pkg.version {
+            args.push("--version".to_string());
+            args.push(version.clone());
+        }
+
+        if !pkg.features.is_empty() {
+            args.push("--features".to_string());
+            args.push(pkg.features.join(","));
+        }
+
+        if pkg.locked {
+            args.push("--locked".to_string());
+        }
+
+        if pkg.channel.is_some() {
+            runner.warn("WARNING: Channels are not supported for cargo packages.\nSkipping channel argument");
+        }
+
+        args
+    }
+}
+
+impl Backend for Cargo {
+    fn is_supported(&self, _runner: &dyn Runner, _os: Type) -> bool {
+        true
+    }
+
+    fn install(&self, runner: &dyn Runner, pkg: &PackageData) -> Result<bool> {
+        let mut cmd = Command::new("cargo");
+
+        cmd.args(Self::install_args(runner, pkg));
+
+        runner.run(cmd)
+    }
+
+    fn uninstall(&self, runner: &dyn Runner, pkg: &PackageData) -> Result<bool> {
+        let mut cmd = Command::new("cargo");
+        let args: Vec<String> = Vec::from(["uninstall".to_string(), pkg.name.clone()]);
+
+        cmd.args(args);
+
+        runner.run(cmd)
+    }
+
+    /// Cargo has no upgrade command, but reinstalling an unpinned crate pulls the newest version
+    fn update(&self, runner: &dyn Runner, pkg: &PackageData) -> Result<bool> {
+        let mut cmd = Command::new("cargo");
+
+        cmd.args(Self::install_args(runner, pkg));
+
+        runner.run(cmd)
+    }
+
+    fn query(&self, runner: &dyn Runner, pkg: &PackageData) -> Result<Option<String>> {
+        let mut cmd = Command::new("cargo");
+        cmd.args(["install", "--list"]);
+
+        // Installed crates are listed as "name v1.2.3:" followed by indented binaries
+        Ok(runner.output(cmd)?.and_then(|out| {
+            out.lines().find_map(|line| {
+                let mut parts = line.split_whitespace();
+
+                if parts.next() != Some(pkg.name.as_str()) {
+                    return None;
+                }
+
+                parts
+                    .next()
+                    .map(|v| v.trim_start_matches('v').trim_end_matches(':').to_string())
+            })
+        }))
+    }
+}
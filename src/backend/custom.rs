@@ -0,0 +1,185 @@
+use mlua::{Function, Lua, LuaSerdeExt, SerializeOptions, Table, Value};
+use os_info::Type;
+use std::fs::read_to_string;
+use std::path::PathBuf;
+
+use super::Backend;
+use crate::package::PackageData;
+use crate::runner::Runner;
+
+use anyhow::{anyhow, bail, Result};
+
+/// A backend defined in lua through `nexus.register_backend(name, { ... })`.
+///
+/// Every function receives the package's data as a table. `install` and
+/// `uninstall` are required, the rest are optional.
+pub struct LuaBackend {
+    name: String,
+    lua: Lua,
+    install: Function,
+    uninstall: Function,
+    update: Option<Function>,
+    is_installed: Option<Function>,
+    supported: Option<Function>,
+}
+
+impl LuaBackend {
+    fn from_table(lua: &Lua, name: String, table: Table) -> mlua::Result<Self> {
+        let required = |key: &str| -> mlua::Result<Function> {
+            table.get::<Option<Function>>(key)?.ok_or_else(|| {
+                mlua::Error::RuntimeError(format!(
+                    "Backend {} is missing an {} function",
+                    name, key
+                ))
+            })
+        };
+
+        Ok(Self {
+            install: required("install")?,
+            uninstall: required("uninstall")?,
+            update: table.get("update")?,
+            is_installed: table.get("is_installed")?,
+            supported: table.get("supported")?,
+            lua: lua.clone(),
+            name,
+        })
+    }
+
+    /// The package as a lua table. Unset fields are left out rather than set to
+    /// mlua's null, which lua would treat as true.
+    fn to_table(&self, pkg: &PackageData) -> Result<Value> {
+        self.lua
+            .to_value_with(pkg, SerializeOptions::new().serialize_none_to_null(false))
+            .map_err(|e| anyhow!("Failed to pass {} to lua: {}", &pkg.name, e))
+    }
+
+    /// Calls `func` with the package as a table. Functions signal failure by
+    /// returning false or raising an error, returning nothing counts as success.
+    fn call(
+        &self,
+        runner: &dyn Runner,
+        key: &str,
+        func: &Function,
+        pkg: &PackageData,
+    ) -> Result<bool> {
+        if runner.is_dry_run() {
+            runner.log(&format!(
+                "Would call the {} backend's {} function for {}",
+                &self.name, key, &pkg.name
+            ));
+            return Ok(true);
+        }
+
+        let table = self.to_table(pkg)?;
+
+        let ret: Option<bool> = func
+            .call(table)
+            .map_err(|e| anyhow!("{} backend failed on {}: {}", &self.name, &pkg.name, e))?;
+
+        Ok(ret.unwrap_or(true))
+    }
+}
+
+impl Backend for LuaBackend {
+    fn is_supported(&self, runner: &dyn Runner, os: Type) -> bool {
+        let Some(func) = &self.supported else {
+            return true;
+        };
+
+        match func.call::<bool>(os.to_string()) {
+            Ok(b) => b,
+            Err(e) => {
+                runner.warn(&format!(
+                    "WARNING: {} backend failed to report support: {}",
+                    &self.name, e
+                ));
+                false
+            }
+        }
+    }
+
+    fn install(&self, runner: &dyn Runner, pkg: &PackageData) -> Result<bool> {
+        self.call(runner, "install", &self.install, pkg)
+    }
+
+    fn uninstall(&self, runner: &dyn Runner, pkg: &PackageData) -> Result<bool> {
+        self.call(runner, "uninstall", &self.uninstall, pkg)
+    }
+
+    fn update(&self, runner: &dyn Runner, pkg: &PackageData) -> Result<bool> {
+        let Some(func) = &self.update else {
+            bail!("{} backend does not support updating", &self.name);
+        };
+
+        self.call(runner, "update", func, pkg)
+    }
+
+    /// `is_installed` may return the installed version as a string or just a boolean
+    fn query(&self, _runner: &dyn Runner, pkg: &PackageData) -> Result<Option<String>> {
+        let Some(func) = &self.is_installed else {
+            return Ok(None);
+        };
+
+        let table = self.to_table(pkg)?;
+
+        let ret: Value = func
+            .call(table)
+            .map_err(|e| anyhow!("{} backend failed on {}: {}", &self.name, &pkg.name, e))?;
+
+        match ret {
+            Value::String(s) => Ok(Some(s.to_string_lossy())),
+            Value::Boolean(true) => Ok(pkg.version.clone()),
+            _ => Ok(None),
+        }
+    }
+}
+
+/// Evaluates the backend files with a `nexus.register_backend` function available
+/// and returns every backend they registered
+pub fn load(lua: &Lua, paths: &[PathBuf]) -> Result<Vec<(String, LuaBackend)>, String> {
+    let registered = lua
+        .create_table()
+        .map_err(|e| format!("Failed to create backend table: {}", e))?;
+
+    let nexus = lua
+        .create_table()
+        .map_err(|e| format!("Failed to create nexus table: {}", e))?;
+
+    let sink = registered.clone();
+    let register = lua
+        .create_function(move |_, (name, spec): (String, Table)| sink.set(name, spec))
+        .map_err(|e| format!("Failed to create register_backend: {}", e))?;
+
+    nexus
+        .set("register_backend", register)
+        .and_then(|_| lua.globals().set("nexus", nexus))
+        .map_err(|e| format!("Failed to expose nexus table to lua: {}", e))?;
+
+    for path in paths {
+        let f = match read_to_string(path) {
+            Ok(f) => f,
+            Err(_) => {
+                return Err(format!("Failed to open {}", path.display()));
+            }
+        };
+
+        if let Err(e) = lua.load(f).exec() {
+            return Err(format!(
+                "Failed to load backends from {}: {}",
+                path.display(),
+                e
+            ));
+        }
+    }
+
+    registered
+        .pairs::<String, Table>()
+        .map(|pair| {
+            let (name, spec) = pair.map_err(|e| format!("Invalid backend definition: {}", e))?;
+            let backend = LuaBackend::from_table(lua, name.clone(), spec)
+                .map_err(|e| format!("Invalid backend {}: {}", name, e))?;
+
+            Ok((name, backend))
+        })
+        .collect()
+}
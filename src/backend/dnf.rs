@@ -0,0 +1,139 @@
+use os_info::Type;
+use std::process::Command;
+
+use super::{command, Backend};
+use crate::package::PackageData;
+use crate::runner::Runner;
+
+use anyhow::Result;
+
+pub struct Dnf;
+
+impl Dnf {
+    /// What to pass to `dnf install` for `pkg`
+    fn install_target(runner: &dyn Runner, pkg: &PackageData) -> String {
+        // A local .rpm is installed by path, its version is whatever the file contains
+        if let Some(source) = &pkg.source {
+            if pkg.version.is_some() {
+                runner.warn(
+                    "WARNING: Versions are ignored for dnf packages installed from a local file.\nSkipping version argument",
+                );
+            }
+
+            return source.clone();
+        }
+
+        match &pkg.version {
+            Some(version) => format!("{}-{}", &pkg.name, version),
+            None => pkg.name.clone(),
+        }
+    }
+}
+
+impl Backend for Dnf {
+    fn is_supported(&self, _runner: &dyn Runner, os: Type) -> bool {
+        matches!(
+            os,
+            Type::Fedora
+                | Type::Redhat
+                | Type::RedHatEnterprise
+                | Type::CentOS
+                | Type::RockyLinux
+                | Type::AlmaLinux
+                | Type::OracleLinux
+                | Type::Nobara
+                | Type::Ultramarine
+        )
+    }
+
+    fn requires_root(&self) -> bool {
+        true
+    }
+
+    fn install(&self, runner: &dyn Runner, pkg: &PackageData) -> Result<bool> {
+        let mut cmd = command(self, "dnf");
+        let mut args: Vec<String> =
+            Vec::from(["install".to_string(), Self::install_target(runner, pkg)]);
+
+        // Dnf has no channels, the closest equivalent is pulling from a specific repo
+        if let Some(channel) = &pkg.channel {
+            args.push(format!("--enablerepo={}", channel));
+        }
+
+        args.push("-y".to_string());
+
+        cmd.args(args);
+
+        runner.run(cmd)
+    }
+
+    /// A repo enabled for one package would apply to the whole batch
+    fn can_batch(&self, pkg: &PackageData) -> bool {
+        pkg.channel.is_none()
+    }
+
+    fn install_batch(&self, runner: &dyn Runner, pkgs: &[&PackageData]) -> Result<bool> {
+        let mut cmd = command(self, "dnf");
+        let mut args: Vec<String> = Vec::from(["install".to_string()]);
+
+        args.extend(pkgs.iter().map(|pkg| Self::install_target(runner, pkg)));
+        args.push("-y".to_string());
+
+        cmd.args(args);
+
+        runner.run(cmd)
+    }
+
+    /// `dnf install` skips a changed local .rpm that kept its version, while
+    /// `dnf reinstall` only takes the installed version. So the reinstall is
+    /// tried first and a file with a new version is installed as usual.
+    fn reinstall(&self, runner: &dyn Runner, pkg: &PackageData) -> Result<bool> {
+        let Some(source) = &pkg.source else {
+            return self.install(runner, pkg);
+        };
+
+        let mut cmd = command(self, "dnf");
+        let args: Vec<String> =
+            Vec::from(["reinstall".to_string(), source.clone(), "-y".to_string()]);
+
+        cmd.args(args);
+
+        if runner.run(cmd)? {
+            return Ok(true);
+        }
+
+        self.install(runner, pkg)
+    }
+
+    fn uninstall(&self, runner: &dyn Runner, pkg: &PackageData) -> Result<bool> {
+        let mut cmd = command(self, "dnf");
+        let args: Vec<String> =
+            Vec::from(["remove".to_string(), pkg.name.clone(), "-y".to_string()]);
+
+        cmd.args(args);
+
+        runner.run(cmd)
+    }
+
+    fn update(&self, runner: &dyn Runner, pkg: &PackageData) -> Result<bool> {
+        let mut cmd = command(self, "dnf");
+        let mut args: Vec<String> = Vec::from(["upgrade".to_string(), pkg.name.clone()]);
+
+        if let Some(channel) = &pkg.channel {
+            args.push(format!("--enablerepo={}", channel));
+        }
+
+        args.push("-y".to_string());
+
+        cmd.args(args);
+
+        runner.run(cmd)
+    }
+
+    fn query(&self, runner: &dyn Runner, pkg: &PackageData) -> Result<Option<String>> {
+        let mut cmd = Command::new("rpm");
+        cmd.args(["-q", "--queryformat", "%{VERSION}-%{RELEASE}", &pkg.name]);
+
+        runner.output(cmd)
+    }
+}
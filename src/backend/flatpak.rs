@@ -0,0 +1,104 @@
+use os_info::Type;
+use std::env::consts::OS;
+use std::process::Command;
+
+use super::Backend;
+use crate::package::{PackageData, Scope};
+use crate::runner::Runner;
+
+use anyhow::Result;
+
+const DEFAULT_REMOTE: &str = "flathub";
+const DEFAULT_BRANCH: &str = "stable";
+
+pub struct Flatpak;
+
+impl Flatpak {
+    fn scope_arg(pkg: &PackageData) -> String {
+        format!("--{}", pkg.scope.unwrap_or(Scope::System))
+    }
+
+    fn install_with(runner: &dyn Runner, pkg: &PackageData, flags: &[&str]) -> Result<bool> {
+        let mut cmd = Command::new("flatpak");
+        let remote = pkg.remote.as_deref().unwrap_or(DEFAULT_REMOTE);
+
+        if pkg.version.is_some() {
+            runner.warn("WARNING: Versions are not supported for flatpak packages. Use channel to select a branch\nSkipping version argument");
+        }
+
+        let mut args: Vec<String> = Vec::from(["install".to_string()]);
+        args.extend(flags.iter().map(|f| f.to_string()));
+        args.extend([
+            Self::scope_arg(pkg),
+            "-y".to_string(),
+            remote.to_string(),
+            Self::app_ref(pkg),
+        ]);
+
+        cmd.args(args);
+
+        runner.run(cmd)
+    }
+
+    /// Builds the `app//branch` ref, using the channel as the branch
+    fn app_ref(pkg: &PackageData) -> String {
+        let branch = pkg.channel.as_deref().unwrap_or(DEFAULT_BRANCH);
+
+        format!("{}//{}", &pkg.name, branch)
+    }
+}
+
+impl Backend for Flatpak {
+    fn is_supported(&self, _runner: &dyn Runner, _os: Type) -> bool {
+        OS == "linux"
+    }
+
+    fn install(&self, runner: &dyn Runner, pkg: &PackageData) -> Result<bool> {
+        Self::install_with(runner, pkg, &[])
+    }
+
+    /// flatpak skips refs that are already installed unless told to reinstall
+    fn reinstall(&self, runner: &dyn Runner, pkg: &PackageData) -> Result<bool> {
+        Self::install_with(runner, pkg, &["--reinstall"])
+    }
+
+    fn uninstall(&self, runner: &dyn Runner, pkg: &PackageData) -> Result<bool> {
+        let mut cmd = Command::new("flatpak");
+        let args: Vec<String> = Vec::from([
+            "uninstall".to_string(),
+            Self::scope_arg(pkg),
+            "-y".to_string(),
+            Self::app_ref(pkg),
+        ]);
+
+        cmd.args(args);
+
+        runner.run(cmd)
+    }
+
+    fn update(&self, runner: &dyn Runner, pkg: &PackageData) -> Result<bool> {
+        let mut cmd = Command::new("flatpak");
+        let args: Vec<String> = Vec::from([
+            "update".to_string(),
+            Self::scope_arg(pkg),
+            "-y".to_string(),
+            Self::app_ref(pkg),
+        ]);
+
+        cmd.args(args);
+
+        runner.run(cmd)
+    }
+
+    fn query(&self, runner: &dyn Runner, pkg: &PackageData) -> Result<Option<String>> {
+        let mut cmd = Command::new("flatpak");
+        cmd.args(["info", &Self::scope_arg(pkg), &Self::app_ref(pkg)]);
+
+        Ok(runner.output(cmd)?.and_then(|out| {
+            out.lines()
+                .map(|line| line.trim())
+                .find_map(|line| line.strip_prefix("Version:"))
+                .map(|v| v.trim().to_string())
+        }))
+    }
+}
@@ -0,0 +1,299 @@
+use mlua::Function;
+use os_info::Type;
+use std::fs::remove_dir_all;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use super::Backend;
+use crate::config::Config;
+use crate::package::{Package, PackageData};
+use crate::runner::Runner;
+
+use anyhow::{anyhow, bail, Result};
+
+/// Clones a git repository into nexus' cache, checks out the requested ref and
+/// builds it from source with the package's `build` and `install` steps.
+/// Its `uninstall` step undoes the install before the checkout is removed.
+pub struct Git {
+    cache_dir: PathBuf,
+}
+
+impl Git {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            cache_dir: config.config_dir.join("git"),
+        }
+    }
+
+    fn checkout_dir(&self, pkg: &PackageData) -> PathBuf {
+        self.cache_dir.join(&pkg.name)
+    }
+
+    fn git(dir: &Path) -> Command {
+        let mut cmd = Command::new("git");
+        cmd.arg("-C").arg(dir);
+        cmd
+    }
+
+    /// Clones the repo on first use, otherwise fetches the latest refs from
+    /// wherever the package points now
+    fn fetch(&self, runner: &dyn Runner, pkg: &PackageData) -> Result<PathBuf> {
+        let Some(repo) = &pkg.repo else {
+            bail!("Git package {} is missing a repo", &pkg.name);
+        };
+
+        let dir = self.checkout_dir(pkg);
+
+        if dir.exists() {
+            let mut cmd = Self::git(&dir);
+            cmd.args(["remote", "set-url", "origin", repo]);
+
+            if !runner.run(cmd)? {
+                bail!("Failed to point {} at {}", &pkg.name, repo);
+            }
+        }
+
+        let mut cmd = if dir.exists() {
+            let mut cmd = Self::git(&dir);
+            cmd.args(["fetch", "--tags", "--force", "origin"]);
+            cmd
+        } else {
+            let mut cmd = Command::new("git");
+            cmd.arg("clone").arg(repo).arg(&dir);
+            cmd
+        };
+
+        cmd.arg("--quiet");
+
+        if !runner.run(cmd)? {
+            bail!("Failed to fetch {}", repo);
+        }
+
+        Ok(dir)
+    }
+
+    /// Resolves the package's ref to a commit, preferring remote branches so
+    /// that a branch name follows upstream rather than the local checkout
+    fn resolve(runner: &dyn Runner, dir: &Path, pkg: &PackageData) -> Result<String> {
+        let git_ref = pkg.git_ref.as_deref().unwrap_or("HEAD");
+
+        for candidate in [format!("origin/{}", git_ref), git_ref.to_string()] {
+            let mut cmd = Self::git(dir);
+            cmd.args(["rev-parse", "--verify", "--quiet"])
+                .arg(format!("{}^{{commit}}", candidate));
+
+            if let Some(commit) = runner.output(cmd)? {
+                return Ok(commit);
+            }
+        }
+
+        bail!("Failed to resolve ref {} for {}", git_ref, &pkg.name)
+    }
+
+    /// During a dry run nothing was fetched, so the ref is left unresolved
+    fn resolve_or_plan(runner: &dyn Runner, dir: &Path, pkg: &PackageData) -> Result<String> {
+        if runner.is_dry_run() {
+            return Ok(pkg.git_ref.clone().unwrap_or("HEAD".to_string()));
+        }
+
+        Self::resolve(runner, dir, pkg)
+    }
+
+    fn shell(command: &str, dir: &Path) -> Command {
+        let mut cmd = if cfg!(windows) {
+            let mut cmd = Command::new("cmd");
+            cmd.arg("/C");
+            cmd
+        } else {
+            let mut cmd = Command::new("sh");
+            cmd.arg("-c");
+            cmd
+        };
+
+        cmd.arg(command).current_dir(dir);
+        cmd
+    }
+
+    /// Runs a step either through its lua function, which gets the checkout dir
+    /// as its argument, or as a list of shell commands inside the checkout
+    fn run_step(
+        runner: &dyn Runner,
+        name: &str,
+        step: &str,
+        func: Option<&Function>,
+        commands: &[String],
+        dir: &Path,
+    ) -> Result<bool> {
+        if let Some(func) = func {
+            if runner.is_dry_run() {
+                runner.log(&format!("Would run {} script", step));
+                return Ok(true);
+            }
+
+            runner.log(&format!("Running {} script", step));
+            func.call::<()>(dir.display().to_string())
+                .map_err(|e| anyhow!("{} step for {} failed: {}", step, name, e))?;
+
+            return Ok(true);
+        }
+
+        for command in commands {
+            if !runner.is_dry_run() {
+                runner.log(&format!("Running {} command: {}", step, command));
+            }
+
+            if !runner.run(Self::shell(command, dir))? {
+                runner.warn(&format!("ERROR: {} command failed for {}", step, name));
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+
+    fn build_and_install(
+        &self,
+        runner: &dyn Runner,
+        pkg: &mut Package,
+        dir: &Path,
+        commit: String,
+    ) -> Result<bool> {
+        let mut cmd = Self::git(dir);
+        cmd.args(["checkout", "--quiet", "--detach", &commit]);
+
+        if !runner.run(cmd)? {
+            bail!(
+                "Failed to check out {} for {}",
+                &commit,
+                &pkg.package_data.name
+            );
+        }
+
+        let data = &pkg.package_data;
+
+        if !Self::run_step(
+            runner,
+            &data.name,
+            "build",
+            pkg.build.as_ref(),
+            &data.build_commands,
+            dir,
+        )? {
+            return Ok(false);
+        }
+
+        if !Self::run_step(
+            runner,
+            &data.name,
+            "install",
+            pkg.install.as_ref(),
+            &data.install_commands,
+            dir,
+        )? {
+            return Ok(false);
+        }
+
+        pkg.package_data.commit = Some(commit);
+
+        Ok(true)
+    }
+}
+
+impl Backend for Git {
+    fn is_supported(&self, _runner: &dyn Runner, _os: Type) -> bool {
+        true
+    }
+
+    fn install(&self, runner: &dyn Runner, pkg: &PackageData) -> Result<bool> {
+        self.install_package(runner, &mut Package::from(pkg.clone()))
+    }
+
+    fn uninstall(&self, runner: &dyn Runner, pkg: &PackageData) -> Result<bool> {
+        let dir = self.checkout_dir(pkg);
+
+        // Without it whatever the install step placed would be left behind
+        if pkg.uninstall_commands.is_empty() {
+            runner.warn(&format!(
+                "WARNING: {} has no uninstall step. Not sure how to undo its install step",
+                &pkg.name
+            ));
+            return Ok(false);
+        }
+
+        if !runner.is_dry_run() && !dir.exists() {
+            bail!(
+                "Checkout of {} is missing, can't run its uninstall step",
+                &pkg.name
+            );
+        }
+
+        if !Self::run_step(
+            runner,
+            &pkg.name,
+            "uninstall",
+            None,
+            &pkg.uninstall_commands,
+            &dir,
+        )? {
+            return Ok(false);
+        }
+
+        if runner.is_dry_run() {
+            runner.log(&format!("Would remove {}", dir.display()));
+            return Ok(true);
+        }
+
+        remove_dir_all(&dir)?;
+
+        Ok(true)
+    }
+
+    fn update(&self, runner: &dyn Runner, pkg: &PackageData) -> Result<bool> {
+        self.update_package(runner, &mut Package::from(pkg.clone()))
+    }
+
+    /// Installing always checks out and rebuilds, so it doubles as a reinstall
+    fn reinstall_package(&self, runner: &dyn Runner, pkg: &mut Package) -> Result<bool> {
+        self.install_package(runner, pkg)
+    }
+
+    fn install_package(&self, runner: &dyn Runner, pkg: &mut Package) -> Result<bool> {
+        if pkg.package_data.version.is_some() {
+            runner.warn("WARNING: Versions are not supported for git packages. Use ref instead\nSkipping version argument");
+        }
+
+        let dir = self.fetch(runner, &pkg.package_data)?;
+        let commit = Self::resolve_or_plan(runner, &dir, &pkg.package_data)?;
+
+        self.build_and_install(runner, pkg, &dir, commit)
+    }
+
+    /// Only rebuilds when the ref has moved since the last build
+    fn update_package(&self, runner: &dyn Runner, pkg: &mut Package) -> Result<bool> {
+        let dir = self.fetch(runner, &pkg.package_data)?;
+        let commit = Self::resolve_or_plan(runner, &dir, &pkg.package_data)?;
+
+        if pkg.package_data.commit.as_ref() == Some(&commit) {
+            runner.log(&format!(
+                "{} is already at {}",
+                &pkg.package_data.name, &commit
+            ));
+            return Ok(true);
+        }
+
+        self.build_and_install(runner, pkg, &dir, commit)
+    }
+
+    fn query(&self, runner: &dyn Runner, pkg: &PackageData) -> Result<Option<String>> {
+        let dir = self.checkout_dir(pkg);
+
+        if !dir.exists() {
+            return Ok(None);
+        }
+
+        let mut cmd = Self::git(&dir);
+        cmd.args(["rev-parse", "HEAD"]);
+
+        runner.output(cmd)
+    }
+}
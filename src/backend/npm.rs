@@ -0,0 +1,82 @@
+use os_info::Type;
+use serde_json::Value;
+
+use super::{command, Backend};
+use crate::package::PackageData;
+use crate::runner::Runner;
+
+use anyhow::Result;
+
+pub struct Npm;
+
+impl Npm {
+    fn registry_args(pkg: &PackageData, args: &mut Vec<String>) {
+        if let Some(registry) = &pkg.registry {
+            args.push(format!("--registry={}", registry));
+        }
+    }
+}
+
+impl Backend for Npm {
+    fn is_supported(&self, _runner: &dyn Runner, _os: Type) -> bool {
+        true
+    }
+
+    fn install(&self, runner: &dyn Runner, pkg: &PackageData) -> Result<bool> {
+        let mut cmd = command(self, "npm");
+        let mut args: Vec<String> = Vec::from(["install".to_string(), "-g".to_string()]);
+
+        if let Some(version) = &pkg.version {
+            args.push(format!("{}@{}", &pkg.name, version));
+        } else {
+            args.push(pkg.name.clone());
+        }
+
+        if pkg.channel.is_some() {
+            runner.warn("WARNING: Channels are not supported for npm packages. Use version to select a dist-tag\nSkipping channel argument");
+        }
+
+        Self::registry_args(pkg, &mut args);
+
+        cmd.args(args);
+
+        runner.run(cmd)
+    }
+
+    fn uninstall(&self, runner: &dyn Runner, pkg: &PackageData) -> Result<bool> {
+        let mut cmd = command(self, "npm");
+        let args: Vec<String> =
+            Vec::from(["uninstall".to_string(), "-g".to_string(), pkg.name.clone()]);
+
+        cmd.args(args);
+
+        runner.run(cmd)
+    }
+
+    fn update(&self, runner: &dyn Runner, pkg: &PackageData) -> Result<bool> {
+        let mut cmd = command(self, "npm");
+        let mut args: Vec<String> =
+            Vec::from(["update".to_string(), "-g".to_string(), pkg.name.clone()]);
+
+        Self::registry_args(pkg, &mut args);
+
+        cmd.args(args);
+
+        runner.run(cmd)
+    }
+
+    fn query(&self, runner: &dyn Runner, pkg: &PackageData) -> Result<Option<String>> {
+        let mut cmd = command(self, "npm");
+        cmd.args(["ls", "-g", "--json", "--depth=0", &pkg.name]);
+
+        let Some(out) = runner.output(cmd)? else {
+            return Ok(None);
+        };
+
+        let json: Value = serde_json::from_str(&out)?;
+
+        Ok(json["dependencies"][&pkg.name]["version"]
+            .as_str()
+            .map(|v| v.to_string()))
+    }
+}
@@ -0,0 +1,164 @@
+use os_info::Type;
+use std::fs::read_dir;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use super::{command, Backend};
+use crate::package::PackageData;
+use crate::runner::Runner;
+
+use anyhow::Result;
+
+const PACKAGE_CACHE: &str = "/var/cache/pacman/pkg";
+
+#[derive(Default)]
+pub struct Pacman {
+    /// Set once this run has upgraded the whole system
+    upgraded: AtomicBool,
+}
+
+impl Pacman {
+    /// Finds a previously downloaded archive of `name` at `version` in pacman's package cache
+    fn cached_package(cache: &Path, name: &str, version: &str) -> Option<PathBuf> {
+        let prefix = format!("{}-{}-", name, version);
+
+        read_dir(cache)
+            .ok()?
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .find(|p| {
+                let file_name = p
+                    .file_name()
+                    .unwrap_or_default()
+                    .to_str()
+                    .unwrap_or_default();
+
+                file_name.starts_with(&prefix)
+                    && (file_name.ends_with(".pkg.tar.zst") || file_name.ends_with(".pkg.tar.xz"))
+            })
+    }
+}
+
+impl Backend for Pacman {
+    fn is_supported(&self, _runner: &dyn Runner, os: Type) -> bool {
+        os == Type::Arch || os == Type::Manjaro || os == Type::EndeavourOS
+    }
+
+    fn requires_root(&self) -> bool {
+        true
+    }
+
+    fn install(&self, runner: &dyn Runner, pkg: &PackageData) -> Result<bool> {
+        let mut cmd = command(self, "pacman");
+        let mut args: Vec<String> = Vec::new();
+
+        let cached = pkg
+            .version
+            .as_ref()
+            .and_then(|v| Self::cached_package(Path::new(PACKAGE_CACHE), &pkg.name, v));
+
+        if let Some(path) = cached {
+            args.push("-U".to_string());
+            args.push(path.display().to_string());
+        } else {
+            if let Some(version) = &pkg.version {
+                runner.warn(&format!(
+                    "WARNING: {} {} is not in the pacman cache. Pacman can only install the latest version\nSkipping version argument",
+                    &pkg.name, version
+                ));
+            }
+
+            args.push("-S".to_string());
+            args.push("--needed".to_string());
+            args.push(pkg.name.clone());
+        }
+
+        if pkg.channel.is_some() {
+            runner.warn("WARNING: Channels are not supported for pacman packages.\nSkipping channel argument");
+        }
+
+        args.push("--noconfirm".to_string());
+
+        cmd.args(args);
+
+        runner.run(cmd)
+    }
+
+    /// Pinned versions are installed from the cache with `-U`, one at a time
+    fn can_batch(&self, pkg: &PackageData) -> bool {
+        pkg.version.is_none()
+    }
+
+    fn install_batch(&self, runner: &dyn Runner, pkgs: &[&PackageData]) -> Result<bool> {
+        let mut cmd = command(self, "pacman");
+        let mut args: Vec<String> = Vec::from(["-S".to_string(), "--needed".to_string()]);
+
+        for pkg in pkgs {
+            if pkg.channel.is_some() {
+                runner.warn("WARNING: Channels are not supported for pacman packages.\nSkipping channel argument");
+            }
+
+            args.push(pkg.name.clone());
+        }
+
+        args.push("--noconfirm".to_string());
+
+        cmd.args(args);
+
+        runner.run(cmd)
+    }
+
+    fn uninstall(&self, runner: &dyn Runner, pkg: &PackageData) -> Result<bool> {
+        let mut cmd = command(self, "pacman");
+        let args: Vec<String> = Vec::from([
+            "-R".to_string(),
+            pkg.name.clone(),
+            "--noconfirm".to_string(),
+        ]);
+
+        cmd.args(args);
+
+        runner.run(cmd)
+    }
+
+    /// Arch doesn't support partial upgrades, so the first update of a run
+    /// refreshes the sync database and upgrades the whole system. Later ones
+    /// only make sure their package is there.
+    fn update(&self, runner: &dyn Runner, pkg: &PackageData) -> Result<bool> {
+        let mut cmd = command(self, "pacman");
+        let upgraded = self.upgraded.load(Ordering::SeqCst);
+        let mut args: Vec<String> = if upgraded {
+            Vec::from(["-S".to_string(), "--needed".to_string()])
+        } else {
+            Vec::from(["-Syu".to_string()])
+        };
+
+        args.push(pkg.name.clone());
+        args.push("--noconfirm".to_string());
+
+        if pkg.channel.is_some() {
+            runner.warn("WARNING: Channels are not supported for pacman packages.\nSkipping channel argument");
+        }
+
+        cmd.args(args);
+
+        let ok = runner.run(cmd)?;
+
+        if ok && !upgraded {
+            self.upgraded.store(true, Ordering::SeqCst);
+        }
+
+        Ok(ok)
+    }
+
+    fn query(&self, runner: &dyn Runner, pkg: &PackageData) -> Result<Option<String>> {
+        let mut cmd = Command::new("pacman");
+        cmd.args(["-Q", &pkg.name]);
+
+        // Output looks like "name 1.2.3-1"
+        Ok(runner
+            .output(cmd)?
+            .and_then(|out| out.split_whitespace().nth(1).map(|v| v.to_string())))
+    }
+}
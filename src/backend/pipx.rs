@@ -0,0 +1,94 @@
+use os_info::Type;
+use serde_json::Value;
+
+use super::{command, Backend};
+use crate::package::PackageData;
+use crate::runner::Runner;
+
+use anyhow::Result;
+
+pub struct Pipx;
+
+impl Pipx {
+    /// Builds a pip requirement such as `name[extra1,extra2]==1.2.3`
+    fn requirement(pkg: &PackageData) -> String {
+        let mut spec = pkg.name.clone();
+
+        if !pkg.extras.is_empty() {
+            spec.push_str(format!("[{}]", pkg.extras.join(",")).as_str());
+        }
+
+        if let Some(version) = &pkg.version {
+            spec.push_str(format!("=={}", version).as_str());
+        }
+
+        spec
+    }
+
+    fn install_with(&self, runner: &dyn Runner, pkg: &PackageData, flags: &[&str]) -> Result<bool> {
+        let mut cmd = command(self, "pipx");
+        let mut args: Vec<String> = Vec::from(["install".to_string()]);
+        args.extend(flags.iter().map(|f| f.to_string()));
+        args.push(Self::requirement(pkg));
+
+        if pkg.channel.is_some() {
+            runner.warn(
+                "WARNING: Channels are not supported for pipx packages.\nSkipping channel argument",
+            );
+        }
+
+        cmd.args(args);
+
+        runner.run(cmd)
+    }
+}
+
+impl Backend for Pipx {
+    fn is_supported(&self, _runner: &dyn Runner, _os: Type) -> bool {
+        true
+    }
+
+    fn install(&self, runner: &dyn Runner, pkg: &PackageData) -> Result<bool> {
+        self.install_with(runner, pkg, &[])
+    }
+
+    /// pipx refuses to install over an existing venv unless forced
+    fn reinstall(&self, runner: &dyn Runner, pkg: &PackageData) -> Result<bool> {
+        self.install_with(runner, pkg, &["--force"])
+    }
+
+    fn uninstall(&self, runner: &dyn Runner, pkg: &PackageData) -> Result<bool> {
+        let mut cmd = command(self, "pipx");
+        let args: Vec<String> = Vec::from(["uninstall".to_string(), pkg.name.clone()]);
+
+        cmd.args(args);
+
+        runner.run(cmd)
+    }
+
+    fn update(&self, runner: &dyn Runner, pkg: &PackageData) -> Result<bool> {
+        let mut cmd = command(self, "pipx");
+        let args: Vec<String> = Vec::from(["upgrade".to_string(), pkg.name.clone()]);
+
+        cmd.args(args);
+
+        runner.run(cmd)
+    }
+
+    fn query(&self, runner: &dyn Runner, pkg: &PackageData) -> Result<Option<String>> {
+        let mut cmd = command(self, "pipx");
+        cmd.args(["list", "--json"]);
+
+        let Some(out) = runner.output(cmd)? else {
+            return Ok(None);
+        };
+
+        let json: Value = serde_json::from_str(&out)?;
+
+        Ok(
+            json["venvs"][&pkg.name]["metadata"]["main_package"]["package_version"]
+                .as_str()
+                .map(|v| v.to_string()),
+        )
+    }
+}
@@ -0,0 +1,98 @@
+use os_info::Type;
+use std::process::Command;
+
+use super::{command, Backend};
+use crate::package::PackageData;
+use crate::runner::Runner;
+
+use anyhow::Result;
+
+pub struct Snap;
+
+impl Snap {
+    /// Builds `--channel=[version/]channel`, defaulting to the stable channel
+    fn channel_arg(pkg: &PackageData) -> String {
+        let mut channel_arg: String = "--channel=".to_string();
+
+        if let Some(version) = &pkg.version {
+            channel_arg.push_str(format!("{}/", version).as_str());
+        }
+
+        if let Some(channel) = &pkg.channel {
+            channel_arg.push_str(channel);
+        } else {
+            channel_arg.push_str("stable");
+        }
+
+        channel_arg
+    }
+}
+
+impl Backend for Snap {
+    fn is_supported(&self, _runner: &dyn Runner, os: Type) -> bool {
+        os == Type::Ubuntu
+    }
+
+    fn requires_root(&self) -> bool {
+        true
+    }
+
+    fn install(&self, runner: &dyn Runner, pkg: &PackageData) -> Result<bool> {
+        let mut cmd = command(self, "snap");
+        let args: Vec<String> = Vec::from([
+            "install".to_string(),
+            pkg.name.clone(),
+            Self::channel_arg(pkg),
+        ]);
+
+        cmd.args(args);
+
+        runner.run(cmd)
+    }
+
+    /// `snap install` leaves an installed snap alone, switching it to another
+    /// channel takes a refresh
+    fn reinstall(&self, runner: &dyn Runner, pkg: &PackageData) -> Result<bool> {
+        let mut cmd = command(self, "snap");
+        let args: Vec<String> = Vec::from([
+            "refresh".to_string(),
+            pkg.name.clone(),
+            Self::channel_arg(pkg),
+        ]);
+
+        cmd.args(args);
+
+        runner.run(cmd)
+    }
+
+    fn uninstall(&self, runner: &dyn Runner, pkg: &PackageData) -> Result<bool> {
+        let mut cmd = command(self, "snap");
+        let args: Vec<String> = Vec::from(["remove".to_string(), pkg.name.clone()]);
+
+        cmd.args(args);
+
+        runner.run(cmd)
+    }
+
+    fn update(&self, runner: &dyn Runner, pkg: &PackageData) -> Result<bool> {
+        let mut cmd = command(self, "snap");
+        let args: Vec<String> = Vec::from(["refresh".to_string(), pkg.name.clone()]);
+
+        cmd.args(args);
+
+        runner.run(cmd)
+    }
+
+    fn query(&self, runner: &dyn Runner, pkg: &PackageData) -> Result<Option<String>> {
+        let mut cmd = Command::new("snap");
+        cmd.args(["list", &pkg.name]);
+
+        // The first line is the table header, the version is the second column
+        Ok(runner.output(cmd)?.and_then(|out| {
+            out.lines()
+                .nth(1)
+                .and_then(|line| line.split_whitespace().nth(1))
+                .map(|v| v.to_string())
+        }))
+    }
+}
@@ -0,0 +1,338 @@
+use os_info::Type;
+use std::env::consts::{ARCH, OS};
+use std::fs::{self, create_dir_all, read_to_string, remove_dir_all, remove_file};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use super::Backend;
+use crate::config::Config;
+use crate::package::{hash_file, ArchiveFormat, PackageData};
+use crate::runner::Runner;
+
+use anyhow::{bail, Context, Result};
+
+/// Downloads a tarball, zip or single binary and places the requested files in
+/// nexus' bin dir. A manifest of the placed files is kept per package so that
+/// uninstalling removes exactly what was installed.
+pub struct Url {
+    bin_dir: PathBuf,
+    manifest_dir: PathBuf,
+    staging_dir: PathBuf,
+}
+
+impl Url {
+    pub fn new(config: &Config) -> Self {
+        let root = config.data_dir.join("url");
+
+        Self {
+            bin_dir: config.data_dir.join("bin"),
+            manifest_dir: root.join("manifests"),
+            staging_dir: root.join("staging"),
+        }
+    }
+
+    fn manifest_path(&self, pkg: &PackageData) -> PathBuf {
+        self.manifest_dir.join(format!("{}.manifest", &pkg.name))
+    }
+
+    fn read_manifest(&self, pkg: &PackageData) -> Result<Option<Vec<PathBuf>>> {
+        match read_to_string(self.manifest_path(pkg)) {
+            Ok(s) => Ok(Some(s.lines().map(PathBuf::from).collect())),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e).context("Failed to read url package manifest"),
+        }
+    }
+
+    /// The other url package whose manifest lists `path`, if any
+    fn owner(&self, pkg: &PackageData, path: &Path) -> Result<Option<String>> {
+        let entries = match fs::read_dir(&self.manifest_dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e).context("Failed to read url package manifests"),
+        };
+
+        for entry in entries {
+            let manifest = entry?.path();
+
+            if manifest == self.manifest_path(pkg)
+                || manifest.extension().is_none_or(|ext| ext != "manifest")
+            {
+                continue;
+            }
+
+            let contents =
+                read_to_string(&manifest).context("Failed to read url package manifest")?;
+
+            if contents.lines().any(|line| Path::new(line) == path) {
+                let name = manifest.file_stem().unwrap_or_default().to_string_lossy();
+                return Ok(Some(name.to_string()));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Fills in the `{name}`, `{version}`, `{os}` and `{arch}` placeholders of the url template
+    fn expand(template: &str, pkg: &PackageData) -> Result<String> {
+        let mut url = template
+            .replace("{name}", &pkg.name)
+            .replace("{os}", OS)
+            .replace("{arch}", ARCH);
+
+        if url.contains("{version}") {
+            let Some(version) = &pkg.version else {
+                bail!(
+                    "Url for {} uses {{version}} but no version was given",
+                    &pkg.name
+                );
+            };
+
+            url = url.replace("{version}", version);
+        }
+
+        Ok(url)
+    }
+
+    fn fetch(runner: &dyn Runner, url: &str, dest: &Path) -> Result<()> {
+        if let Some(path) = url.strip_prefix("file://") {
+            fs::copy(path, dest).with_context(|| format!("Failed to copy {}", path))?;
+            return Ok(());
+        }
+
+        let mut cmd = Command::new("curl");
+        cmd.args(["-fsSL", "-o"]).arg(dest).arg(url);
+
+        if !runner.run(cmd)? {
+            bail!("Failed to download {}", url);
+        }
+
+        Ok(())
+    }
+
+    fn extract(
+        runner: &dyn Runner,
+        format: ArchiveFormat,
+        archive: &Path,
+        dest: &Path,
+    ) -> Result<()> {
+        create_dir_all(dest)?;
+
+        let mut cmd = match format {
+            ArchiveFormat::TarGz | ArchiveFormat::TarXz => {
+                let flags = if format == ArchiveFormat::TarGz {
+                    "-xzf"
+                } else {
+                    "-xJf"
+                };
+
+                let mut cmd = Command::new("tar");
+                cmd.arg(flags).arg(archive).arg("-C").arg(dest);
+                cmd
+            }
+            ArchiveFormat::Zip => {
+                let mut cmd = Command::new("unzip");
+                cmd.arg("-q").arg(archive).arg("-d").arg(dest);
+                cmd
+            }
+            ArchiveFormat::Raw => return Ok(()),
+        };
+
+        cmd.stdin(Stdio::null());
+
+        if !runner.run(cmd)? {
+            bail!("Failed to extract {}", archive.display());
+        }
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    fn make_executable(path: &Path) -> Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+
+        fs::set_permissions(path, fs::Permissions::from_mode(0o755))?;
+
+        Ok(())
+    }
+
+    #[cfg(not(unix))]
+    fn make_executable(_path: &Path) -> Result<()> {
+        Ok(())
+    }
+
+    fn file_name(path: &str) -> Result<String> {
+        Path::new(path)
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .with_context(|| format!("Invalid file name: {}", path))
+    }
+}
+
+impl Backend for Url {
+    fn is_supported(&self, _runner: &dyn Runner, _os: Type) -> bool {
+        true
+    }
+
+    fn install(&self, runner: &dyn Runner, pkg: &PackageData) -> Result<bool> {
+        let Some(template) = &pkg.url else {
+            bail!("Url package {} is missing a url", &pkg.name);
+        };
+
+        let Some(expected) = &pkg.sha256 else {
+            bail!("Url package {} is missing a sha256", &pkg.name);
+        };
+
+        if pkg.channel.is_some() {
+            runner.warn(
+                "WARNING: Channels are not supported for url packages.\nSkipping channel argument",
+            );
+        }
+
+        let url = Self::expand(template, pkg)?;
+
+        if runner.is_dry_run() {
+            runner.log(&format!(
+                "Would download {} and place [{}] into {}",
+                url,
+                pkg.files.join(", "),
+                self.bin_dir.display()
+            ));
+            return Ok(true);
+        }
+
+        let staging = self.staging_dir.join(&pkg.name);
+
+        if staging.exists() {
+            remove_dir_all(&staging)?;
+        }
+
+        create_dir_all(&staging)?;
+
+        let download = staging.join("download");
+
+        runner.log(&format!("Downloading {}", url));
+        Self::fetch(runner, &url, &download)?;
+
+        let actual = hash_file(&download)?;
+
+        if !actual.eq_ignore_ascii_case(expected) {
+            runner.warn(&format!(
+                "ERROR: Checksum mismatch for {}\nExpected: {}\nActual:   {}",
+                &pkg.name, expected, actual
+            ));
+            let _ = remove_dir_all(&staging);
+            bail!("Checksum mismatch for {}", url);
+        }
+
+        let format = pkg.format.unwrap_or(ArchiveFormat::Raw);
+
+        // Pairs of (file to copy, name to give it in the bin dir)
+        let sources: Vec<(PathBuf, String)> = if format == ArchiveFormat::Raw {
+            let file_name = match pkg.files.first() {
+                Some(f) => Self::file_name(f)?,
+                None => pkg.name.clone(),
+            };
+
+            Vec::from([(download.clone(), file_name)])
+        } else {
+            if pkg.files.is_empty() {
+                bail!(
+                    "Url package {} does not list any files to extract",
+                    &pkg.name
+                );
+            }
+
+            let extracted = staging.join("extract");
+            Self::extract(runner, format, &download, &extracted)?;
+
+            pkg.files
+                .iter()
+                .map(|f| Ok((extracted.join(f), Self::file_name(f)?)))
+                .collect::<Result<_>>()?
+        };
+
+        // The bin dir is shared, so placing a file another package shipped
+        // would clobber it, and uninstalling either would remove it for both
+        for (_, file_name) in &sources {
+            let dest = self.bin_dir.join(file_name);
+
+            if let Some(owner) = self.owner(pkg, &dest)? {
+                let _ = remove_dir_all(&staging);
+                bail!(
+                    "Refusing to overwrite {}: it belongs to url package {}",
+                    dest.display(),
+                    owner
+                );
+            }
+        }
+
+        create_dir_all(&self.bin_dir)?;
+
+        let previous = self.read_manifest(pkg)?.unwrap_or_default();
+        let mut placed = Vec::<PathBuf>::new();
+
+        for (src, file_name) in sources {
+            let dest = self.bin_dir.join(file_name);
+
+            fs::copy(&src, &dest).with_context(|| {
+                format!("Failed to place {} into {}", src.display(), dest.display())
+            })?;
+            Self::make_executable(&dest)?;
+
+            placed.push(dest);
+        }
+
+        // Files from an older install that this version no longer ships
+        for old in previous.iter().filter(|p| !placed.contains(p)) {
+            let _ = remove_file(old);
+        }
+
+        create_dir_all(&self.manifest_dir)?;
+
+        let manifest: Vec<String> = placed.iter().map(|p| p.display().to_string()).collect();
+        fs::write(self.manifest_path(pkg), manifest.join("\n"))
+            .context("Failed to write url package manifest")?;
+
+        let _ = remove_dir_all(&staging);
+
+        Ok(true)
+    }
+
+    fn uninstall(&self, runner: &dyn Runner, pkg: &PackageData) -> Result<bool> {
+        let Some(placed) = self.read_manifest(pkg)? else {
+            runner.warn(&format!(
+                "WARNING: No manifest found for {}. Not sure which files to remove",
+                &pkg.name
+            ));
+            return Ok(false);
+        };
+
+        if runner.is_dry_run() {
+            for path in placed {
+                runner.log(&format!("Would remove {}", path.display()));
+            }
+            return Ok(true);
+        }
+
+        for path in placed {
+            match remove_file(&path) {
+                Ok(_) => {}
+                Err(e) if e.kind() == ErrorKind::NotFound => {}
+                Err(e) => {
+                    return Err(e).with_context(|| format!("Failed to remove {}", path.display()))
+                }
+            }
+        }
+
+        remove_file(self.manifest_path(pkg)).context("Failed to remove url package manifest")?;
+
+        Ok(true)
+    }
+
+    /// Downloads and places the files again. The sha256 pins what gets
+    /// installed, so a new release takes a new sha256 (and usually version).
+    fn update(&self, runner: &dyn Runner, pkg: &PackageData) -> Result<bool> {
+        self.install(runner, pkg)
+    }
+}
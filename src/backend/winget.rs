@@ -0,0 +1,65 @@
+use os_info::Type;
+use std::process::Command;
+
+use super::Backend;
+use crate::package::PackageData;
+use crate::runner::Runner;
+
+use anyhow::Result;
+
+pub struct Winget;
+
+impl Backend for Winget {
+    fn is_supported(&self, _runner: &dyn Runner, os: Type) -> bool {
+        os == Type::Windows
+    }
+
+    fn install(&self, runner: &dyn Runner, pkg: &PackageData) -> Result<bool> {
+        let mut cmd = Command::new("winget");
+        let mut args: Vec<String> = Vec::from(["install".to_string(), pkg.name.clone()]);
+
+        if let Some(version) = &pkg.version {
+            args.push("--version".to_string());
+            args.push(version.clone());
+        }
+
+        if pkg.channel.is_some() {
+            runner.warn("WARNING: Channels are not supported for winget packages\nSkipping channel argument");
+        }
+
+        cmd.args(args);
+
+        runner.run(cmd)
+    }
+
+    fn uninstall(&self, runner: &dyn Runner, pkg: &PackageData) -> Result<bool> {
+        let mut cmd = Command::new("winget");
+        let mut args: Vec<String> = Vec::from(["uninstall".to_string(), pkg.name.clone()]);
+
+        if let Some(version) = &pkg.version {
+            args.push("--version".to_string());
+            args.push(version.clone());
+        }
+
+        if pkg.channel.is_some() {
+            runner.warn("WARNING: Channels are not supported for winget packages\nSkipping channel argument");
+        }
+
+        cmd.args(args);
+
+        runner.run(cmd)
+    }
+
+    fn update(&self, runner: &dyn Runner, pkg: &PackageData) -> Result<bool> {
+        let mut cmd = Command::new("winget");
+        let args: Vec<String> = Vec::from(["upgrade".to_string(), pkg.name.clone()]);
+
+        if pkg.channel.is_some() {
+            runner.warn("WARNING: Channels are not supported for winget packages\nSkipping channel argument");
+        }
+
+        cmd.args(args);
+
+        runner.run(cmd)
+    }
+}
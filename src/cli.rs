@@ -5,6 +5,18 @@ use clap::{Args, Parser, Subcommand};
 pub struct Cli {
     #[command(subcommand)]
     pub command: Commands,
+
+    /// Print what would be done without running any package manager or changing state
+    #[arg(long, global = true, default_value_t = false)]
+    pub dry_run: bool,
+
+    /// Wait for another running nexus to finish instead of failing
+    #[arg(long, global = true, default_value_t = false)]
+    pub wait: bool,
+
+    /// Install packages of up to this many different backends at the same time
+    #[arg(short, long, global = true, default_value_t = 1)]
+    pub jobs: usize,
 }
 
 #[derive(Subcommand)]
@@ -23,6 +35,20 @@ pub enum Commands {
     /// Update all installed packages known by nexus
     #[command(visible_aliases = ["upgrade", "u", "refresh"])]
     Update,
+
+    /// Install, purge and update in one pass so the system matches your config/packages directory
+    #[command(visible_aliases = ["s"])]
+    Sync,
+
+    /// Show everything sync would do without doing it
+    Plan,
+
+    /// Restore the installed packages to an earlier generation, by default the one before the latest
+    Rollback(RollbackArgs),
+
+    /// Show what nexus has installed, uninstalled and updated so far
+    #[command(visible_aliases = ["h", "log"])]
+    History(HistoryArgs),
 }
 
 #[derive(Args)]
@@ -31,3 +57,28 @@ pub struct ListArgs {
     #[arg(short, long, default_value_t = false)]
     pub installed: bool,
 }
+
+#[derive(Args)]
+pub struct HistoryArgs {
+    /// Show only operations on packages with this name
+    #[arg(short, long)]
+    pub package: Option<String>,
+
+    /// Show only operations from this day (YYYY-MM-DD, UTC) onwards
+    #[arg(short, long)]
+    pub since: Option<String>,
+
+    /// Print the entries as json
+    #[arg(long, default_value_t = false)]
+    pub json: bool,
+}
+
+#[derive(Args)]
+pub struct RollbackArgs {
+    /// The generation to restore
+    pub generation: Option<u64>,
+
+    /// List the recorded generations instead
+    #[arg(short, long, default_value_t = false, conflicts_with = "generation")]
+    pub list: bool,
+}
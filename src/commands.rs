@@ -0,0 +1,594 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::backend::Registry;
+use crate::cli::{HistoryArgs, RollbackArgs};
+use crate::config::Config;
+use crate::history::{self, Entry};
+use crate::package::{InstallInfo, Package, PackageData};
+use crate::package_manager;
+use crate::plan::{self, Action};
+use crate::state;
+
+/// Writes the installed packages to disk, unless this is a dry run
+fn save_state(registry: &Registry, config: &Config, installed_packages: &[PackageData]) {
+    if registry.runner().is_dry_run() {
+        return;
+    }
+
+    if let Err(e) = state::save(config, installed_packages) {
+        eprintln!("WARNING: {}. Expect limited functionality", e);
+    }
+}
+
+/// Writes the installed packages partway through a run, leaving the backup
+/// taken at its start alone. Does nothing during a dry run.
+fn save_progress(registry: &Registry, config: &Config, installed_packages: &[PackageData]) {
+    if registry.runner().is_dry_run() {
+        return;
+    }
+
+    if let Err(e) = state::write(config, installed_packages) {
+        eprintln!("WARNING: {}. Expect limited functionality", e);
+    }
+}
+
+/// Records the state a run ended with as a new generation to roll back to,
+/// unless this is a dry run
+fn save_generation(registry: &Registry, config: &Config, installed_packages: &[PackageData]) {
+    if registry.runner().is_dry_run() {
+        return;
+    }
+
+    if let Err(e) = state::save_generation(config, installed_packages) {
+        eprintln!("WARNING: {}. This run can't be rolled back to", e);
+    }
+}
+
+pub fn install(registry: &Registry, config: &Config, pkgs: &[Package]) -> Result<(), String> {
+    let mut installed_packages = state::load(config)?;
+    let mut pending = Vec::<Package>::new();
+
+    for action in plan::install(pkgs, &installed_packages) {
+        if registry.runner().is_dry_run() {
+            println!("Would {}", action);
+        }
+
+        let pkg = match action {
+            Action::Skip(data) => {
+                println!("{}: Skipped because of same hash", data.name);
+                continue;
+            }
+            Action::Reinstall(mut pkg) => {
+                println!(
+                    "{}: Reinstalling because its source file changed",
+                    pkg.package_data.name
+                );
+                pkg.reinstall = true;
+                pkg
+            }
+            Action::Modify(mut pkg, _) => {
+                println!(
+                    "{}: Reinstalling because its definition changed",
+                    pkg.package_data.name
+                );
+                pkg.reinstall = true;
+                pkg
+            }
+            Action::Install(pkg) => pkg,
+            _ => continue,
+        };
+
+        println!("Found pkg: {}", pkg.package_data.name);
+        pending.push(pkg);
+    }
+
+    let results = package_manager::install_many(registry, &mut pending);
+
+    for (pkg, result) in pending.into_iter().zip(results) {
+        match result {
+            Ok(true) => record(registry, &mut installed_packages, pkg.package_data),
+            Ok(false) => {}
+            Err(e) => {
+                eprintln!("ERROR: Failed to install {}: {}", &pkg.package_data.name, e);
+            }
+        }
+    }
+
+    save_state(registry, config, &installed_packages);
+    save_generation(registry, config, &installed_packages);
+
+    Ok(())
+}
+
+pub fn purge(registry: &Registry, config: &Config, pkgs: &[Package]) -> Result<(), String> {
+    let mut installed_pkgs = state::load(config)?;
+    let mut uninstalled_pkgs = Vec::<PackageData>::new();
+
+    for action in plan::purge(pkgs, &installed_pkgs) {
+        if registry.runner().is_dry_run() {
+            println!("Would {}", action);
+        }
+
+        let pkg_data = match action {
+            Action::Remove(data) => data,
+            Action::Needed(data, by) => {
+                println!("{}: Not removed because {} depends on it", data.name, by);
+                continue;
+            }
+            _ => continue,
+        };
+
+        // A package the backend didn't remove stays recorded so it can be retried
+        match package_manager::uninstall(registry, &pkg_data) {
+            Ok(true) => {
+                println!("Successfully uninstalled {}", pkg_data.name);
+                uninstalled_pkgs.push(pkg_data);
+            }
+            Ok(false) => {
+                eprintln!("Failed to uninstall: {}. Not sure why...", pkg_data.name);
+            }
+            Err(e) => {
+                eprintln!("Failed to uninstall {}: {}", pkg_data.name, e);
+            }
+        }
+    }
+
+    for pkg in uninstalled_pkgs {
+        installed_pkgs.retain(|p| !p.is_same_package(&pkg));
+    }
+
+    save_state(registry, config, &installed_pkgs);
+    save_generation(registry, config, &installed_pkgs);
+
+    Ok(())
+}
+
+pub fn update(registry: &Registry, config: &Config, pkgs: &[Package]) -> Result<(), String> {
+    let mut installed_packages = state::load(config)?;
+
+    for action in plan::update(pkgs, &installed_packages) {
+        if registry.runner().is_dry_run() {
+            println!("Would {}", action);
+        }
+
+        let Action::Upgrade(mut pkg) = action else {
+            continue;
+        };
+
+        match package_manager::update(registry, &mut pkg) {
+            Ok(b) => {
+                if b {
+                    println!("Successfully updated {}", &pkg.package_data.name);
+
+                    record(registry, &mut installed_packages, pkg.package_data);
+                } else {
+                    eprintln!(
+                        "Failed to update: {}. Not sure why...",
+                        &pkg.package_data.name
+                    );
+                }
+            }
+            Err(e) => {
+                eprintln!("Failed to update: {} {}", &pkg.package_data.name, e);
+            }
+        };
+    }
+
+    save_state(registry, config, &installed_packages);
+    save_generation(registry, config, &installed_packages);
+
+    Ok(())
+}
+
+/// What a sync did, printed once at the end
+#[derive(Default)]
+struct Summary {
+    installed: Vec<String>,
+    modified: Vec<String>,
+    removed: Vec<String>,
+    upgraded: Vec<String>,
+    kept: Vec<String>,
+    pinned: usize,
+    failed: Vec<String>,
+}
+
+impl Summary {
+    fn print(&self, dry_run: bool) {
+        println!(
+            "{}: {} installed, {} modified, {} removed, {} upgraded, {} pinned",
+            if dry_run {
+                "Sync plan"
+            } else {
+                "Sync complete"
+            },
+            self.installed.len(),
+            self.modified.len(),
+            self.removed.len(),
+            self.upgraded.len(),
+            self.pinned,
+        );
+
+        for (label, names) in [
+            ("Installed", &self.installed),
+            ("Modified", &self.modified),
+            ("Removed", &self.removed),
+            ("Upgraded", &self.upgraded),
+            ("Kept as dependencies", &self.kept),
+        ] {
+            if !names.is_empty() {
+                println!("{}: {}", label, names.join(", "));
+            }
+        }
+
+        if !self.failed.is_empty() {
+            eprintln!("Failed: {}", self.failed.join(", "));
+        }
+    }
+}
+
+/// Replaces the entry of the same package, or records `data` as a new one,
+/// noting when and at which version it was installed
+fn record(registry: &Registry, installed_packages: &mut Vec<PackageData>, mut data: PackageData) {
+    let version = if registry.runner().is_dry_run() {
+        None
+    } else {
+        package_manager::query(registry, &data).ok().flatten()
+    };
+
+    data.installed = Some(InstallInfo {
+        installed_at: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default(),
+        nexus_version: env!("CARGO_PKG_VERSION").to_string(),
+        version,
+    });
+
+    match installed_packages
+        .iter()
+        .position(|p| p.is_same_package(&data))
+    {
+        Some(idx) => installed_packages[idx] = data,
+        None => installed_packages.push(data),
+    }
+}
+
+/// Installs the queued packages and records the outcome
+fn flush_installs(
+    registry: &Registry,
+    pending: &mut Vec<Package>,
+    installed_packages: &mut Vec<PackageData>,
+    summary: &mut Summary,
+) {
+    if pending.is_empty() {
+        return;
+    }
+
+    let results = package_manager::install_many(registry, pending);
+
+    for (pkg, result) in pending.drain(..).zip(results) {
+        let name = pkg.package_data.name.clone();
+
+        match result {
+            Ok(true) => {
+                if pkg.reinstall {
+                    summary.modified.push(name);
+                } else {
+                    summary.installed.push(name);
+                }
+
+                record(registry, installed_packages, pkg.package_data);
+            }
+            Ok(false) => summary.failed.push(name),
+            Err(e) => {
+                eprintln!("ERROR: Failed to install {}: {}", name, e);
+                summary.failed.push(name);
+            }
+        }
+    }
+}
+
+/// Brings the installed packages in line with the declared ones in a single
+/// pass: removals, then additions, then changed definitions, then upgrades.
+pub fn sync(registry: &Registry, config: &Config, pkgs: &[Package]) -> Result<(), String> {
+    let mut installed_packages = state::load(config)?;
+    let mut summary = Summary::default();
+
+    // The state is saved as the run goes, so the backup is taken once up front
+    if !registry.runner().is_dry_run() {
+        if let Err(e) = state::backup(config) {
+            eprintln!("WARNING: {}", e);
+        }
+    }
+
+    // Installs are held back so consecutive ones can be batched
+    let mut pending = Vec::<Package>::new();
+
+    for action in plan::sync(pkgs, &installed_packages) {
+        if registry.runner().is_dry_run() {
+            println!("Would {}", action);
+        }
+
+        if !matches!(
+            action,
+            Action::Install(_) | Action::Modify(..) | Action::Reinstall(_)
+        ) {
+            flush_installs(
+                registry,
+                &mut pending,
+                &mut installed_packages,
+                &mut summary,
+            );
+        }
+
+        match action {
+            Action::Remove(data) => match package_manager::uninstall(registry, &data) {
+                Ok(true) => {
+                    installed_packages.retain(|p| !p.is_same_package(&data));
+                    summary.removed.push(data.name);
+                }
+                Ok(false) => {
+                    eprintln!("Failed to uninstall: {}. Not sure why...", data.name);
+                    summary.failed.push(data.name);
+                }
+                Err(e) => {
+                    eprintln!("Failed to uninstall {}: {}", data.name, e);
+                    summary.failed.push(data.name);
+                }
+            },
+            Action::Install(pkg) => pending.push(pkg),
+            Action::Modify(mut pkg, _) | Action::Reinstall(mut pkg) => {
+                pkg.reinstall = true;
+                pending.push(pkg);
+            }
+            Action::Upgrade(mut pkg) => match package_manager::update(registry, &mut pkg) {
+                Ok(true) => {
+                    summary.upgraded.push(pkg.package_data.name.clone());
+                    record(registry, &mut installed_packages, pkg.package_data);
+                }
+                Ok(false) => {
+                    eprintln!(
+                        "Failed to update: {}. Not sure why...",
+                        &pkg.package_data.name
+                    );
+                    summary.failed.push(pkg.package_data.name);
+                }
+                Err(e) => {
+                    eprintln!("Failed to update: {} {}", &pkg.package_data.name, e);
+                    summary.failed.push(pkg.package_data.name);
+                }
+            },
+            Action::Needed(data, by) => {
+                println!("{}: Not removed because {} depends on it", data.name, by);
+                summary.kept.push(data.name);
+            }
+            Action::Pinned(_) => summary.pinned += 1,
+            _ => {}
+        }
+
+        save_progress(registry, config, &installed_packages);
+    }
+
+    flush_installs(
+        registry,
+        &mut pending,
+        &mut installed_packages,
+        &mut summary,
+    );
+    save_progress(registry, config, &installed_packages);
+    save_generation(registry, config, &installed_packages);
+
+    summary.print(registry.runner().is_dry_run());
+
+    Ok(())
+}
+
+/// Prints the recorded generations, marking the latest one
+fn print_generations(config: &Config) -> Result<(), String> {
+    let generations = state::generations(config)?;
+
+    if generations.is_empty() {
+        println!("No generations recorded yet");
+    }
+
+    for (idx, generation) in generations.iter().enumerate() {
+        let pkgs = state::load_generation(config, *generation)?;
+
+        println!(
+            "{}: {} packages{}",
+            generation,
+            pkgs.len(),
+            if idx + 1 == generations.len() {
+                " (current)"
+            } else {
+                ""
+            }
+        );
+    }
+
+    Ok(())
+}
+
+/// Whether the backend of `data` can install a specific version of it
+fn can_pin(registry: &Registry, data: &PackageData) -> bool {
+    registry
+        .get(&data.package_type)
+        .is_ok_and(|b| b.can_pin(data))
+}
+
+/// Installs `pkgs` as recorded in a generation. Where the backend allows it,
+/// each is pinned to the version that was installed back then.
+fn restore(registry: &Registry, pkgs: &mut [Package]) -> Vec<anyhow::Result<bool>> {
+    let mut pinned = Vec::with_capacity(pkgs.len());
+
+    for pkg in pkgs.iter_mut() {
+        let data = &mut pkg.package_data;
+        let version = data
+            .installed
+            .as_ref()
+            .and_then(|i| i.version.clone())
+            .filter(|_| data.version.is_none() && can_pin(registry, data));
+
+        pinned.push(version.is_some());
+        data.version = data.version.take().or(version);
+    }
+
+    let results = package_manager::install_many(registry, pkgs);
+
+    // The pin only applies to this install, the recorded definition stays unpinned
+    for (pkg, pinned) in pkgs.iter_mut().zip(pinned) {
+        if pinned {
+            pkg.package_data.version = None;
+        }
+    }
+
+    results
+}
+
+/// Brings the installed packages back to an earlier generation, by default
+/// the one before the latest
+pub fn rollback(
+    registry: &Registry,
+    config: &Config,
+    pkgs: &[Package],
+    args: &RollbackArgs,
+) -> Result<(), String> {
+    if args.list {
+        return print_generations(config);
+    }
+
+    let generations = state::generations(config)?;
+
+    let target = match args.generation {
+        Some(generation) => generation,
+        None => match generations[..] {
+            [.., previous, _] => previous,
+            _ => return Err("No earlier generation to roll back to".to_string()),
+        },
+    };
+
+    let target_pkgs = state::load_generation(config, target)?;
+    let mut installed_packages = state::load(config)?;
+    let mut pending = Vec::<Package>::new();
+    let mut failed = false;
+
+    for action in plan::rollback(pkgs, &installed_packages, &target_pkgs) {
+        if registry.runner().is_dry_run() {
+            println!("Would {}", action);
+        }
+
+        match action {
+            Action::Remove(data) => match package_manager::uninstall(registry, &data) {
+                Ok(true) => installed_packages.retain(|p| !p.is_same_package(&data)),
+                Ok(false) => {
+                    eprintln!("Failed to uninstall: {}. Not sure why...", data.name);
+                    failed = true;
+                }
+                Err(e) => {
+                    eprintln!("Failed to uninstall {}: {}", data.name, e);
+                    failed = true;
+                }
+            },
+            Action::Modify(pkg, fields)
+                if fields == ["installed version"] && !can_pin(registry, &pkg.package_data) =>
+            {
+                println!(
+                    "{}: Left at its current version since {} packages can't be pinned",
+                    pkg.package_data.name, pkg.package_data.package_type
+                );
+            }
+            Action::Install(pkg) => pending.push(pkg),
+            Action::Modify(mut pkg, _) => {
+                pkg.reinstall = true;
+                pending.push(pkg);
+            }
+            _ => {}
+        }
+    }
+
+    let results = restore(registry, &mut pending);
+
+    for (pkg, result) in pending.into_iter().zip(results) {
+        match result {
+            Ok(true) => record(registry, &mut installed_packages, pkg.package_data),
+            Ok(false) => failed = true,
+            Err(e) => {
+                eprintln!("ERROR: Failed to install {}: {}", &pkg.package_data.name, e);
+                failed = true;
+            }
+        }
+    }
+
+    save_state(registry, config, &installed_packages);
+    save_generation(registry, config, &installed_packages);
+
+    if failed {
+        return Err(format!("Generation {} was only partially restored", target));
+    }
+
+    if !registry.runner().is_dry_run() {
+        println!("Rolled back to generation {}", target);
+    }
+
+    Ok(())
+}
+
+fn print_entry(entry: &Entry) {
+    println!(
+        "{} {} {} [{}] {} ({:.1}s)",
+        history::format_timestamp(entry.timestamp),
+        entry.operation,
+        entry.name,
+        entry.package_type,
+        if entry.success { "ok" } else { "failed" },
+        entry.duration_ms as f64 / 1000.0
+    );
+
+    for cmd in &entry.commands {
+        let exit = match cmd.exit_code {
+            Some(code) => format!("exit {}", code),
+            None => "no exit code".to_string(),
+        };
+
+        println!(
+            "    $ {} ({}, {:.1}s)",
+            cmd.command,
+            exit,
+            cmd.duration_ms as f64 / 1000.0
+        );
+    }
+
+    for hook in &entry.hooks {
+        match &hook.error {
+            Some(e) => println!("    {} script failed: {}", hook.hook, e),
+            None => println!("    {} script ok", hook.hook),
+        }
+    }
+
+    if let Some(e) = &entry.error {
+        println!("    error: {}", e);
+    }
+}
+
+/// Prints the history log, oldest first, narrowed down by `args`
+pub fn history(config: &Config, args: &HistoryArgs) -> Result<(), String> {
+    let since = args.since.as_deref().map(history::parse_date).transpose()?;
+
+    let entries: Vec<Entry> = history::load(config)?
+        .into_iter()
+        .filter(|e| args.package.as_ref().is_none_or(|p| &e.name == p))
+        .filter(|e| since.is_none_or(|since| e.timestamp >= since))
+        .collect();
+
+    if args.json {
+        let json = serde_json::to_string_pretty(&entries)
+            .map_err(|e| format!("Failed to serialize history: {}", e))?;
+        println!("{}", json);
+    } else if entries.is_empty() {
+        println!("No history found");
+    } else {
+        entries.iter().for_each(print_entry);
+    }
+
+    Ok(())
+}
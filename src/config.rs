@@ -1,12 +1,14 @@
-use dirs::config_dir;
+use dirs::{config_dir, data_dir};
 use std::env::consts::OS;
-use std::fs::create_dir;
+use std::fs::{create_dir, create_dir_all};
 use std::path::{Path, PathBuf};
 use walkdir::WalkDir;
 
 pub struct Config {
     pub packages: Vec<PathBuf>,
+    pub backends: Vec<PathBuf>,
     pub config_dir: PathBuf,
+    pub data_dir: PathBuf,
 }
 
 impl Config {
@@ -16,7 +18,7 @@ impl Config {
         } else {
             match OS {
                 "linux" => Path::new("/opt/nexus").to_path_buf(),
-                _ => todo!(),
+                _ => return Err(format!("Failed to find a config dir for {}", OS)),
             }
         };
 
@@ -29,32 +31,55 @@ impl Config {
             return Err("Failed to create packages dir".to_string());
         }
 
-        let packages: Vec<PathBuf> = WalkDir::new(config_dir.join("packages"))
-            .into_iter()
-            .skip(1)
-            .filter_map(|e| e.ok())
-            .map(|e| PathBuf::from(e.path()))
-            .filter(|p| {
-                if p.extension()
-                    .unwrap_or_default()
-                    .to_str()
-                    .unwrap_or_default()
-                    == "lua"
-                {
-                    true
-                } else {
-                    eprintln!(
-                        "WARNING: Found non-lua file {} in packages directory. Ignoring it.",
-                        p.display()
-                    );
-                    false
-                }
-            })
-            .collect();
+        let data_dir = match data_dir() {
+            Some(path) => path.join("nexus"),
+            None => config_dir.clone(),
+        };
+
+        if !data_dir.exists() && create_dir_all(&data_dir).is_err() {
+            return Err("Failed to create data dir".to_string());
+        }
+
+        if !config_dir.join("backends").exists() && create_dir(config_dir.join("backends")).is_err()
+        {
+            return Err("Failed to create backends dir".to_string());
+        }
+
+        let packages = lua_files(&config_dir.join("packages"), "packages");
+        let backends = lua_files(&config_dir.join("backends"), "backends");
 
         Ok(Self {
             packages,
+            backends,
             config_dir,
+            data_dir,
         })
     }
 }
+
+/// Collects every lua file within `dir`, warning about anything else found there
+fn lua_files(dir: &Path, name: &str) -> Vec<PathBuf> {
+    WalkDir::new(dir)
+        .into_iter()
+        .skip(1)
+        .filter_map(|e| e.ok())
+        .map(|e| PathBuf::from(e.path()))
+        .filter(|p| {
+            if p.extension()
+                .unwrap_or_default()
+                .to_str()
+                .unwrap_or_default()
+                == "lua"
+            {
+                true
+            } else {
+                eprintln!(
+                    "WARNING: Found non-lua file {} in {} directory. Ignoring it.",
+                    p.display(),
+                    name
+                );
+                false
+            }
+        })
+        .collect()
+}
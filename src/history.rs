@@ -0,0 +1,285 @@
+use std::fs::{read_to_string, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+use crate::package::{PackageData, PackageType};
+use crate::runner::{display_command, Runner};
+
+/// What was done to a package
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Operation {
+    Install,
+    Uninstall,
+    Update,
+}
+
+impl std::fmt::Display for Operation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Install => write!(f, "install"),
+            Self::Uninstall => write!(f, "uninstall"),
+            Self::Update => write!(f, "update"),
+        }
+    }
+}
+
+/// A command run on behalf of a package
+#[derive(Serialize, Deserialize, Clone)]
+pub struct CommandRecord {
+    pub command: String,
+    /// `None` if the command was killed or could not be started
+    pub exit_code: Option<i32>,
+    pub duration_ms: u64,
+}
+
+/// How one of a package's lua hooks went
+#[derive(Serialize, Deserialize, Clone)]
+pub struct HookRecord {
+    pub hook: String,
+    pub success: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// One operation on one package, as stored in the history log
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Entry {
+    /// Seconds since the unix epoch
+    pub timestamp: u64,
+    pub operation: Operation,
+    pub name: String,
+    pub package_type: PackageType,
+    pub commands: Vec<CommandRecord>,
+    pub hooks: Vec<HookRecord>,
+    pub success: bool,
+    pub duration_ms: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl Entry {
+    pub fn new(
+        operation: Operation,
+        pkg: &PackageData,
+        commands: Vec<CommandRecord>,
+        hooks: Vec<HookRecord>,
+        started: Instant,
+        result: &Result<bool>,
+    ) -> Self {
+        Self {
+            timestamp: now(),
+            operation,
+            name: pkg.name.clone(),
+            package_type: pkg.package_type.clone(),
+            commands,
+            hooks,
+            success: matches!(result, Ok(true)),
+            duration_ms: millis(started.elapsed()),
+            error: result.as_ref().err().map(|e| e.to_string()),
+        }
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+fn millis(duration: Duration) -> u64 {
+    duration.as_millis().try_into().unwrap_or(u64::MAX)
+}
+
+pub fn path(config: &Config) -> PathBuf {
+    config.config_dir.join("history.jsonl")
+}
+
+/// The append-only log of every operation nexus ran, one json entry per line
+pub struct History {
+    path: PathBuf,
+    /// Keeps entries written by parallel jobs from interleaving
+    lock: Mutex<()>,
+}
+
+impl History {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            path: path(config),
+            lock: Mutex::new(()),
+        }
+    }
+
+    pub fn append(&self, entry: &Entry) -> Result<(), String> {
+        let line = serde_json::to_string(entry)
+            .map_err(|e| format!("Failed to serialize history entry: {}", e))?;
+        let _guard = self.lock.lock().unwrap();
+
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .and_then(|mut file| writeln!(file, "{}", line))
+            .map_err(|e| format!("Failed to write {}: {}", self.path.display(), e))
+    }
+}
+
+/// Reads every entry of the history log, oldest first. Lines that can't be
+/// parsed, such as one cut short by a crash, are skipped with a warning.
+pub fn load(config: &Config) -> Result<Vec<Entry>, String> {
+    let path = path(config);
+
+    let contents = match read_to_string(&path) {
+        Ok(c) => c,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+    };
+
+    Ok(contents
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .filter_map(|(idx, line)| match serde_json::from_str(line) {
+            Ok(entry) => Some(entry),
+            Err(e) => {
+                eprintln!(
+                    "WARNING: Skipping line {} of {}: {}",
+                    idx + 1,
+                    path.display(),
+                    e
+                );
+                None
+            }
+        })
+        .collect())
+}
+
+/// Passes commands through to another runner, keeping track of what was run,
+/// how long it took and how it exited
+pub struct HistoryRunner<'a> {
+    inner: &'a dyn Runner,
+    commands: Mutex<Vec<CommandRecord>>,
+}
+
+impl<'a> HistoryRunner<'a> {
+    pub fn new(inner: &'a dyn Runner) -> Self {
+        Self {
+            inner,
+            commands: Mutex::new(vec![]),
+        }
+    }
+
+    /// Every command run so far
+    pub fn commands(&self) -> Vec<CommandRecord> {
+        self.commands.lock().unwrap().clone()
+    }
+}
+
+impl Runner for HistoryRunner<'_> {
+    fn run(&self, cmd: Command) -> Result<bool> {
+        Ok(self.run_with_code(cmd)? == Some(0))
+    }
+
+    fn run_with_code(&self, cmd: Command) -> Result<Option<i32>> {
+        let command = display_command(&cmd);
+        let started = Instant::now();
+        let result = self.inner.run_with_code(cmd);
+
+        self.commands.lock().unwrap().push(CommandRecord {
+            command,
+            exit_code: result.as_ref().ok().copied().flatten(),
+            duration_ms: millis(started.elapsed()),
+        });
+
+        result
+    }
+
+    fn output(&self, cmd: Command) -> Result<Option<String>> {
+        self.inner.output(cmd)
+    }
+
+    fn log(&self, msg: &str) {
+        self.inner.log(msg);
+    }
+
+    fn warn(&self, msg: &str) {
+        self.inner.warn(msg);
+    }
+
+    fn is_dry_run(&self) -> bool {
+        self.inner.is_dry_run()
+    }
+}
+
+/// Days since the unix epoch of a date in the proleptic gregorian calendar
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    era * 146097 + day_of_era - 719468
+}
+
+/// The date `days` after the unix epoch, as (year, month, day)
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+
+    (year_of_era + era * 400 + i64::from(month <= 2), month, day)
+}
+
+/// Parses a `YYYY-MM-DD` date into the unix timestamp of its start, in UTC
+pub fn parse_date(date: &str) -> Result<u64, String> {
+    let invalid = || format!("Invalid date {}. Expected YYYY-MM-DD", date);
+
+    let parts: Vec<i64> = date
+        .split('-')
+        .map(|p| p.parse::<i64>().map_err(|_| invalid()))
+        .collect::<Result<_, _>>()?;
+
+    let [year, month, day] = parts[..] else {
+        return Err(invalid());
+    };
+
+    let days = days_from_civil(year, month, day);
+
+    // Rejects days past the end of the month, which would otherwise roll over
+    if year < 1970 || civil_from_days(days) != (year, month, day) {
+        return Err(invalid());
+    }
+
+    Ok(days as u64 * 86400)
+}
+
+/// Renders a unix timestamp as `YYYY-MM-DD HH:MM:SS` in UTC
+pub fn format_timestamp(timestamp: u64) -> String {
+    let (year, month, day) = civil_from_days((timestamp / 86400) as i64);
+    let secs = timestamp % 86400;
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+        year,
+        month,
+        day,
+        secs / 3600,
+        secs % 3600 / 60,
+        secs % 60
+    )
+}
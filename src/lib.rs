@@ -0,0 +1,11 @@
+pub mod backend;
+pub mod cli;
+pub mod commands;
+pub mod config;
+pub mod history;
+pub mod lock;
+pub mod package;
+pub mod package_manager;
+pub mod plan;
+pub mod runner;
+pub mod state;
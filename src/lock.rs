@@ -0,0 +1,126 @@
+use std::fs::{self, read_to_string};
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use std::process;
+#[cfg(all(unix, not(target_os = "linux")))]
+use std::process::{Command, Stdio};
+use std::thread::sleep;
+use std::time::Duration;
+
+use crate::config::Config;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Keeps other nexus processes from changing packages or state at the same
+/// time. The lock file holds the PID of its owner and is removed on drop.
+pub struct Lock {
+    path: PathBuf,
+}
+
+pub fn path(config: &Config) -> PathBuf {
+    config.config_dir.join("nexus.lock")
+}
+
+/// Whether a process with this PID is still running. procfs is always there,
+/// unlike `ps` which minimal images leave out.
+#[cfg(target_os = "linux")]
+fn is_running(pid: u32) -> bool {
+    PathBuf::from("/proc").join(pid.to_string()).exists()
+}
+
+/// Whether a process with this PID is still running
+#[cfg(all(unix, not(target_os = "linux")))]
+fn is_running(pid: u32) -> bool {
+    Command::new("ps")
+        .args(["-p", &pid.to_string()])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map(|s| s.success())
+        .unwrap_or(true)
+}
+
+/// There is no portable way to check without extra dependencies, so a lock
+/// left behind has to be removed by hand
+#[cfg(not(unix))]
+fn is_running(_pid: u32) -> bool {
+    true
+}
+
+impl Lock {
+    /// Takes the lock, or fails naming the process holding it. With `wait`
+    /// this blocks until the holder is done instead.
+    pub fn acquire(config: &Config, wait: bool) -> Result<Self, String> {
+        let path = path(config);
+        let mut waiting = false;
+
+        loop {
+            let Some(holder) = Self::try_acquire(&path)? else {
+                return Ok(Self { path });
+            };
+
+            if !is_running(holder) {
+                eprintln!(
+                    "WARNING: Removing stale lock left behind by nexus (PID {})",
+                    holder
+                );
+
+                // Only remove it if nobody replaced it in the meantime
+                if Self::holder(&path).unwrap_or(0) == holder {
+                    let _ = fs::remove_file(&path);
+                }
+
+                continue;
+            }
+
+            if !wait {
+                return Err(format!(
+                    "Another nexus (PID {}) is running. Wait for it to finish or pass --wait",
+                    holder
+                ));
+            }
+
+            if !waiting {
+                println!("Waiting for nexus (PID {}) to finish", holder);
+                waiting = true;
+            }
+
+            sleep(POLL_INTERVAL);
+        }
+    }
+
+    /// Creates the lock file, or returns the PID of its current holder. The PID
+    /// is written to a file of our own which is then linked into place, so the
+    /// lock never exists without its holder's PID.
+    fn try_acquire(path: &PathBuf) -> Result<Option<u32>, String> {
+        let pid = process::id();
+        let tmp = path.with_extension(format!("lock.{}", pid));
+
+        fs::write(&tmp, pid.to_string())
+            .map_err(|e| format!("Failed to create {}: {}", tmp.display(), e))?;
+
+        let linked = fs::hard_link(&tmp, path);
+        let _ = fs::remove_file(&tmp);
+
+        match linked {
+            Ok(_) => Ok(None),
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+                // A holder whose PID can't be read is treated as gone
+                Ok(Some(Self::holder(path).unwrap_or(0)))
+            }
+            Err(e) => Err(format!("Failed to create {}: {}", path.display(), e)),
+        }
+    }
+
+    fn holder(path: &PathBuf) -> Option<u32> {
+        read_to_string(path).ok()?.trim().parse().ok()
+    }
+}
+
+impl Drop for Lock {
+    fn drop(&mut self) {
+        if Self::holder(&self.path) == Some(process::id()) {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
@@ -1,18 +1,14 @@
-mod cli;
-mod config;
-mod package;
-mod package_manager;
-
-use std::fs::File;
-use std::io::prelude::*;
 use std::process::exit;
 
 use clap::Parser;
-use cli::{Cli, Commands};
-use config::Config;
 use mlua::Lua;
-
-use crate::package::PackageData;
+use nexus::backend::Registry;
+use nexus::cli::{Cli, Commands};
+use nexus::commands::{history, install, purge, rollback, sync, update};
+use nexus::config::Config;
+use nexus::lock::Lock;
+use nexus::runner::{DryRunner, Runner, SystemRunner};
+use nexus::{package, package_manager, state};
 
 fn main() {
     let lua = Lua::new();
@@ -26,6 +22,30 @@ fn main() {
         }
     };
 
+    // Only reads the log, so it works even if packages or backends fail to load
+    if let Commands::History(args) = &cli.command {
+        if let Err(e) = history(&config, args) {
+            eprintln!("ERROR: {}", e);
+            exit(4);
+        }
+
+        return;
+    }
+
+    let runner: Box<dyn Runner> = if cli.dry_run || matches!(cli.command, Commands::Plan) {
+        Box::new(DryRunner)
+    } else {
+        Box::new(SystemRunner)
+    };
+
+    let mut registry = Registry::new(&config, runner);
+    registry.set_jobs(cli.jobs);
+
+    if let Err(e) = registry.load_lua_backends(&lua, &config) {
+        eprintln!("ERROR: Failed to load backends: {}", e);
+        exit(3);
+    }
+
     let pkgs = match package::get_packages(&lua, &config) {
         Ok(p) => p,
         Err(e) => {
@@ -34,68 +54,51 @@ fn main() {
         }
     };
 
-    match &cli.command {
-        Commands::Install => {
-            let mut installed_packages = package::get_installed_packages(&config);
-            for pkg in pkgs {
-                if installed_packages
-                    .iter()
-                    .any(|p| pkg.package_data.hash == p.hash)
-                {
-                    println!("{}: Skipped because of same hash", pkg.package_data.name);
-                    continue;
-                }
-
-                println!("Found pkg: {}", pkg.package_data.name);
-
-                match package_manager::install(&pkg) {
-                    Ok(good) => {
-                        if good {
-                            installed_packages.push(pkg.package_data);
-                        }
-                    }
-                    Err(e) => {
-                        eprintln!("ERROR: Failed to install {}: {}", &pkg.package_data.name, e);
-                    }
-                }
-
-                let file = match File::create(config.config_dir.join("installed_packages.json")) {
-                    Ok(f) => Some(f),
-                    Err(_) => {
-                        eprintln!("WARNING: Failed to create installed packages file. Expect limited functionality");
-                        None
-                    }
-                };
-
-                if let Some(mut file) = file {
-                    let json = match serde_json::to_string_pretty(&installed_packages) {
-                        Ok(j) => Some(j),
-                        Err(_) => {
-                            eprintln!("WARNING: Failed to serialize installed packages");
-                            None
-                        }
-                    };
-
-                    if let Some(json) = json {
-                        match file.write_all(json.as_bytes()) {
-                            Ok(_) => {}
-                            Err(_) => {
-                                eprintln!("WARNING: Failed to write to installed packages file. Expect limited functionality");
-                            }
-                        }
-                    }
-                }
+    let mutating = matches!(
+        cli.command,
+        Commands::Install
+            | Commands::Purge
+            | Commands::Update
+            | Commands::Sync
+            | Commands::Rollback(_)
+    );
+
+    // Held until the end of main, exiting early leaves a stale lock behind
+    // which the next run cleans up
+    let lock = if mutating && !cli.dry_run {
+        match Lock::acquire(&config, cli.wait) {
+            Ok(lock) => Some(lock),
+            Err(e) => {
+                eprintln!("ERROR: {}", e);
+                exit(5);
             }
         }
+    } else {
+        None
+    };
+
+    let result = match &cli.command {
+        Commands::Install => install(&registry, &config, &pkgs),
         Commands::List(args) => {
             let pkgs = if args.installed {
-                package::get_installed_packages(&config)
+                match state::load(&config) {
+                    Ok(p) => p,
+                    Err(e) => {
+                        eprintln!("ERROR: Failed to load installed packages: {}", e);
+                        exit(4);
+                    }
+                }
             } else {
                 pkgs.iter().map(|pkg| pkg.package_data.clone()).collect()
             };
 
             for pkg in pkgs {
-                let version = match pkg.version {
+                let installed_version = if args.installed {
+                    package_manager::query(&registry, &pkg).ok().flatten()
+                } else {
+                    None
+                };
+                let version = match installed_version.or(pkg.version) {
                     Some(v) => v,
                     None => "latest".to_string(),
                 };
@@ -109,87 +112,19 @@ fn main() {
                     &pkg.name, &pkg.package_type, version, channel
                 );
             }
-        }
-        Commands::Purge => {
-            let mut installed_pkgs = package::get_installed_packages(&config);
-            let mut uninstalled_pkgs = Vec::<PackageData>::new();
-
-            for pkg_data in &installed_pkgs {
-                if pkgs.iter().any(|p| p.package_data == *pkg_data) {
-                    continue;
-                }
-                match package_manager::uninstall(pkg_data) {
-                    Ok(b) => {
-                        if b {
-                            println!("Successfully uninstalled {}", pkg_data.name);
-                        } else {
-                            eprintln!("Failed to uninstall: {}. Not sure why...", pkg_data.name);
-                        }
-
-                        uninstalled_pkgs.push(pkg_data.clone());
-                    }
-                    Err(e) => {
-                        eprintln!("Failed to uninstall {}: {}", pkg_data.name, e);
-                    }
-                }
-            }
-
-            for pkg in uninstalled_pkgs {
-                let Some(idx) = installed_pkgs.iter().position(|p| p == &pkg) else {
-                    continue;
-                };
-
-                installed_pkgs.remove(idx);
-            }
-
-            let file = match File::create(config.config_dir.join("installed_packages.json")) {
-                Ok(f) => Some(f),
-                Err(_) => {
-                    eprintln!("WARNING: Failed to create installed packages file. Expect limited functionality");
-                    None
-                }
-            };
 
-            if let Some(mut file) = file {
-                let json = match serde_json::to_string_pretty(&installed_pkgs) {
-                    Ok(j) => Some(j),
-                    Err(_) => {
-                        eprintln!("WARNING: Failed to serialize installed packages. Expect limited functionality");
-                        None
-                    }
-                };
-
-                if let Some(json) = json {
-                    match file.write_all(json.as_bytes()) {
-                        Ok(_) => {}
-                        Err(_) => {
-                            eprintln!("WARNING: Failed to write to installed packages file. Expect limited functionality");
-                        }
-                    }
-                }
-            }
+            Ok(())
         }
-        Commands::Update => {
-            let installed_packages = package::get_installed_packages(&config);
-
-            for pkg_data in installed_packages {
-                if pkg_data.version.is_some() {
-                    continue;
-                }
+        Commands::Purge => purge(&registry, &config, &pkgs),
+        Commands::Update => update(&registry, &config, &pkgs),
+        Commands::Sync | Commands::Plan => sync(&registry, &config, &pkgs),
+        Commands::Rollback(args) => rollback(&registry, &config, &pkgs, args),
+        Commands::History(_) => unreachable!("handled before packages are loaded"),
+    };
 
-                match package_manager::update(&pkg_data) {
-                    Ok(b) => {
-                        if b {
-                            println!("Successfully updated {}", &pkg_data.name);
-                        } else {
-                            eprintln!("Failed to update: {}. Not sure why...", &pkg_data.name);
-                        }
-                    }
-                    Err(e) => {
-                        eprintln!("Failed to update: {} {}", &pkg_data.name, e);
-                    }
-                };
-            }
-        }
+    if let Err(e) = result {
+        eprintln!("ERROR: {}", e);
+        drop(lock);
+        exit(4);
     }
 }
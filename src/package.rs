@@ -1,20 +1,32 @@
-use mlua::{FromLua, Function, Lua, LuaSerdeExt, Table};
+use mlua::{FromLua, Function, Lua, LuaSerdeExt, Table, Value};
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 
 use std::fmt::Display;
 use std::fs::{read_to_string, File};
 use std::io;
+use std::path::{Path, PathBuf};
 
 use crate::config::Config;
 
-#[derive(Serialize, Deserialize, Clone, PartialEq)]
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
 #[serde(rename_all = "lowercase")]
 pub enum PackageType {
     Apt,
     Snap,
     Brew,
     Winget,
+    Pacman,
+    Dnf,
+    Flatpak,
+    Cargo,
+    Pipx,
+    Npm,
+    Url,
+    Git,
+    /// A backend registered from lua with `nexus.register_backend`
+    #[serde(untagged)]
+    Custom(String),
 }
 
 impl Display for PackageType {
@@ -24,16 +36,118 @@ impl Display for PackageType {
             Self::Snap => write!(f, "snap"),
             Self::Brew => write!(f, "brew"),
             Self::Winget => write!(f, "winget"),
+            Self::Pacman => write!(f, "pacman"),
+            Self::Dnf => write!(f, "dnf"),
+            Self::Flatpak => write!(f, "flatpak"),
+            Self::Cargo => write!(f, "cargo"),
+            Self::Pipx => write!(f, "pipx"),
+            Self::Npm => write!(f, "npm"),
+            Self::Url => write!(f, "url"),
+            Self::Git => write!(f, "git"),
+            Self::Custom(name) => write!(f, "{}", name),
         }
     }
 }
 
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Scope {
+    User,
+    System,
+}
+
+impl Display for Scope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::User => write!(f, "user"),
+            Self::System => write!(f, "system"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum ArchiveFormat {
+    #[serde(rename = "tar.gz")]
+    TarGz,
+    #[serde(rename = "tar.xz")]
+    TarXz,
+    #[serde(rename = "zip")]
+    Zip,
+    #[serde(rename = "raw")]
+    Raw,
+}
+
+/// What nexus knows about an installed package beyond its definition
+#[derive(Clone, Serialize, Deserialize, PartialEq)]
+pub struct InstallInfo {
+    /// Seconds since the unix epoch
+    pub installed_at: u64,
+    /// Version of nexus that installed it
+    pub nexus_version: String,
+    /// Version the backend reported after installing, if it can tell
+    pub version: Option<String>,
+}
+
 #[derive(Clone, Serialize, Deserialize, PartialEq)]
 pub struct PackageData {
     pub name: String,
     pub package_type: PackageType,
     pub version: Option<String>,
     pub channel: Option<String>,
+    #[serde(default)]
+    pub remote: Option<String>,
+    #[serde(default)]
+    pub scope: Option<Scope>,
+    #[serde(default)]
+    pub features: Vec<String>,
+    #[serde(default)]
+    pub git: Option<String>,
+    #[serde(default)]
+    pub path: Option<String>,
+    #[serde(default)]
+    pub locked: bool,
+    #[serde(default)]
+    pub extras: Vec<String>,
+    #[serde(default)]
+    pub registry: Option<String>,
+    #[serde(default)]
+    pub url: Option<String>,
+    #[serde(default)]
+    pub sha256: Option<String>,
+    #[serde(default)]
+    pub format: Option<ArchiveFormat>,
+    #[serde(default)]
+    pub files: Vec<String>,
+    #[serde(default)]
+    pub source: Option<String>,
+    #[serde(default)]
+    pub source_hash: Option<String>,
+    #[serde(default)]
+    pub repo: Option<String>,
+    #[serde(default)]
+    pub git_ref: Option<String>,
+    #[serde(default)]
+    pub build_commands: Vec<String>,
+    #[serde(default)]
+    pub install_commands: Vec<String>,
+    #[serde(default)]
+    pub uninstall_commands: Vec<String>,
+    #[serde(default)]
+    pub commit: Option<String>,
+    /// Names of packages that have to be installed before this one
+    #[serde(default)]
+    pub depends_on: Vec<String>,
+    /// The lua file the package was declared in
+    #[serde(default)]
+    pub file: Option<String>,
+    /// Hash of the lua file the package was declared in. Only kept for
+    /// diagnostics, [`PackageData::hash`] is what decides whether it changed.
+    #[serde(default)]
+    pub file_hash: Option<String>,
+    /// Recorded when nexus installs the package
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub installed: Option<InstallInfo>,
+    /// Hash of everything the package declares, see [`hash_definition`]
     pub hash: String,
 }
 
@@ -42,6 +156,86 @@ pub struct Package {
     pub package_data: PackageData,
     pub pre_install: Option<Function>,
     pub post_install: Option<Function>,
+    pub build: Option<Function>,
+    pub install: Option<Function>,
+    /// Set when the install replaces an installed package whose definition
+    /// changed, see [`crate::backend::Backend::reinstall`]
+    pub reinstall: bool,
+}
+
+impl PackageData {
+    /// Whether both refer to the same package, which is decided by its backend
+    /// and name alone. How it is defined may change over time.
+    pub fn is_same_package(&self, other: &PackageData) -> bool {
+        self.package_type == other.package_type && self.name == other.name
+    }
+
+    /// Only what the package declares, leaving out the fields nexus fills in
+    /// itself such as the hash of a local source file or a resolved commit
+    fn definition(&self) -> PackageData {
+        PackageData {
+            source_hash: None,
+            commit: None,
+            file: None,
+            file_hash: None,
+            installed: None,
+            hash: String::new(),
+            ..self.clone()
+        }
+    }
+
+    /// Names of the declared fields that differ between both
+    pub fn changed_fields(&self, other: &PackageData) -> Vec<String> {
+        let strip =
+            |data: &PackageData| serde_json::to_value(data.definition()).unwrap_or_default();
+
+        let (serde_json::Value::Object(a), serde_json::Value::Object(b)) =
+            (strip(self), strip(other))
+        else {
+            return vec![];
+        };
+
+        a.iter()
+            .filter(|(key, value)| b.get(key.as_str()) != Some(value))
+            .map(|(key, _)| key.clone())
+            .collect()
+    }
+
+    /// Whether `declared` is the definition this package was installed from.
+    /// Packages installed before hashes were computed per package carry the
+    /// hash of their file instead.
+    pub fn same_definition(&self, declared: &PackageData) -> bool {
+        self.hash == declared.hash
+            || (self.file_hash.is_none() && Some(&self.hash) == declared.file_hash.as_ref())
+    }
+}
+
+impl From<PackageData> for Package {
+    fn from(package_data: PackageData) -> Self {
+        Self {
+            package_data,
+            pre_install: None,
+            post_install: None,
+            build: None,
+            install: None,
+            reinstall: false,
+        }
+    }
+}
+
+/// A step such as `build` can either be a lua function or a list of shell commands
+fn get_step(table: &Table, key: &str) -> mlua::Result<(Option<Function>, Vec<String>)> {
+    match table.get::<Value>(key)? {
+        Value::Nil => Ok((None, vec![])),
+        Value::Function(f) => Ok((Some(f), vec![])),
+        Value::String(s) => Ok((None, vec![s.to_str()?.to_string()])),
+        Value::Table(t) => Ok((None, t.sequence_values().collect::<mlua::Result<_>>()?)),
+        other => Err(mlua::Error::RuntimeError(format!(
+            "Expected {} to be a function or a list of commands, got {}",
+            key,
+            other.type_name()
+        ))),
+    }
 }
 
 impl FromLua for Package {
@@ -54,12 +248,7 @@ impl FromLua for Package {
 
         let path = &path_wrapper.0;
 
-        let mut file = File::open(path)?;
-        let mut hasher = Sha256::new();
-
-        io::copy(&mut file, &mut hasher)?;
-
-        let hash = format!("{:x}", hasher.finalize());
+        let file_hash = hash_file(Path::new(path))?;
 
         let table: Table = Table::from_lua(value.clone(), lua)?;
 
@@ -67,27 +256,275 @@ impl FromLua for Package {
         let package_type: PackageType = lua.from_value(table.get("package_type")?)?;
         let version: Option<String> = table.get("version")?;
         let channel: Option<String> = table.get("channel")?;
+        let remote: Option<String> = table.get("remote")?;
+        let scope: Option<Scope> = lua.from_value(table.get("scope")?)?;
+        let features: Vec<String> = table
+            .get::<Option<Vec<String>>>("features")?
+            .unwrap_or_default();
+        let git: Option<String> = table.get("git")?;
+        let locked: bool = table.get::<Option<bool>>("locked")?.unwrap_or(false);
+        let extras: Vec<String> = table
+            .get::<Option<Vec<String>>>("extras")?
+            .unwrap_or_default();
+        let registry: Option<String> = table.get("registry")?;
+        let url: Option<String> = table.get("url")?;
+        let sha256: Option<String> = table.get("sha256")?;
+        let format: Option<ArchiveFormat> = lua.from_value(table.get("format")?)?;
+        let files: Vec<String> = table
+            .get::<Option<Vec<String>>>("files")?
+            .unwrap_or_default();
+
+        // Local paths are relative to the lua file declaring them
+        let relative = |p: String| {
+            Path::new(path)
+                .parent()
+                .unwrap_or(Path::new(""))
+                .join(p)
+                .display()
+                .to_string()
+        };
+        let pkg_path: Option<String> = table.get::<Option<String>>("path")?.map(relative);
+        let source: Option<String> = table.get::<Option<String>>("source")?.map(relative);
+        let source_hash: Option<String> = match &source {
+            Some(s) => Some(hash_file(Path::new(s))?),
+            None => None,
+        };
+
+        let repo: Option<String> = table.get("repo")?;
+        let git_ref: Option<String> = table.get("ref")?;
+        let (build, build_commands) = get_step(&table, "build")?;
+        let (install, install_commands) = get_step(&table, "install")?;
+        let (uninstall, uninstall_commands) = get_step(&table, "uninstall")?;
+
+        // Uninstalling works off the recorded data, the package's file may be gone by then
+        if uninstall.is_some() {
+            return Err(mlua::Error::RuntimeError(format!(
+                "The uninstall step of {} has to be a list of commands",
+                name
+            )));
+        }
+
+        let depends_on: Vec<String> = table
+            .get::<Option<Vec<String>>>("depends_on")?
+            .unwrap_or_default();
         let pre_install: Option<Function> = table.get("pre_install")?;
         let post_install: Option<Function> = table.get("post_install")?;
 
-        Ok(Self {
+        let mut pkg = Self {
             package_data: PackageData {
                 name,
                 package_type,
                 version,
                 channel,
-                hash,
+                remote,
+                scope,
+                features,
+                git,
+                path: pkg_path,
+                locked,
+                extras,
+                registry,
+                url,
+                sha256,
+                format,
+                files,
+                source,
+                source_hash,
+                repo,
+                git_ref,
+                build_commands,
+                install_commands,
+                uninstall_commands,
+                commit: None,
+                depends_on,
+                file: Some(path.clone()),
+                file_hash: Some(file_hash),
+                installed: None,
+                hash: String::new(),
             },
             pre_install,
             post_install,
-        })
+            build,
+            install,
+            reinstall: false,
+        };
+
+        pkg.package_data.hash = hash_definition(&pkg);
+
+        Ok(pkg)
     }
 }
 
 struct FilePathAppData(pub String);
 
+pub fn hash_file(path: &Path) -> io::Result<String> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+
+    io::copy(&mut file, &mut hasher)?;
+
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Makes lua's `package` table callable so that a file can declare packages
+/// with `package { ... }`. Returns the table every declared package is added to.
+fn package_constructor(lua: &Lua) -> mlua::Result<Table> {
+    let declared = lua.create_table()?;
+    let sink = declared.clone();
+
+    let call = lua.create_function(move |_, (_, spec): (Value, Table)| {
+        sink.push(spec.clone())?;
+        Ok(spec)
+    })?;
+
+    let meta = lua.create_table()?;
+    meta.set("__call", call)?;
+
+    let package: Table = lua.globals().get("package")?;
+    package.set_metatable(Some(meta))?;
+
+    Ok(declared)
+}
+
+/// Evaluates a package file, which may return a single package table, a list
+/// of them, or declare them through `package { ... }`
+fn load_file(lua: &Lua, path: &Path, f: String) -> mlua::Result<Vec<Table>> {
+    let declared = package_constructor(lua)?;
+    let returned: Value = lua
+        .load(f)
+        .set_name(format!("@{}", path.display()))
+        .eval()?;
+
+    let mut tables: Vec<Table> = declared.sequence_values().collect::<mlua::Result<_>>()?;
+
+    match returned {
+        Value::Table(t) if t.contains_key("name")? => tables.push(t),
+        Value::Table(t) => {
+            for t in t.sequence_values::<Table>() {
+                tables.push(t?);
+            }
+        }
+        _ => {}
+    }
+
+    // `return package { ... }` hands back a table that was already declared
+    let mut seen = Vec::new();
+    tables.retain(|t| {
+        let new = !seen.contains(&t.to_pointer());
+        seen.push(t.to_pointer());
+        new
+    });
+
+    Ok(tables)
+}
+
+/// Strips comments and collapses whitespace so that only changes to the code
+/// itself make it look different
+fn normalize_lua(src: &str) -> String {
+    let mut out = String::new();
+    let mut chars = src.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '-' if chars.peek() == Some(&'-') => {
+                chars.next();
+
+                let rest: String = chars.clone().take(2).collect();
+
+                if rest == "[[" {
+                    // Block comment, runs until the closing brackets
+                    let mut prev = ' ';
+                    for c in chars.by_ref() {
+                        if prev == ']' && c == ']' {
+                            break;
+                        }
+                        prev = c;
+                    }
+                } else {
+                    for c in chars.by_ref() {
+                        if c == '\n' {
+                            break;
+                        }
+                    }
+                }
+
+                out.push(' ');
+            }
+            '"' | '\'' => {
+                out.push(c);
+
+                while let Some(s) = chars.next() {
+                    out.push(s);
+
+                    if s == '\\' {
+                        if let Some(escaped) = chars.next() {
+                            out.push(escaped);
+                        }
+                    } else if s == c {
+                        break;
+                    }
+                }
+            }
+            c if c.is_whitespace() => out.push(' '),
+            c => out.push(c),
+        }
+    }
+
+    out.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Identifies the code of a hook. Functions defined in a file are identified
+/// by the normalized source of the lines they span, anything else falls back
+/// to its bytecode.
+fn fingerprint(func: &Function) -> Vec<u8> {
+    let info = func.info();
+
+    let source = info
+        .source
+        .as_deref()
+        .and_then(|s| s.strip_prefix('@'))
+        .and_then(|path| read_to_string(path).ok())
+        .zip(info.line_defined.zip(info.last_line_defined))
+        .map(|(src, (first, last))| {
+            let lines: Vec<&str> = src
+                .lines()
+                .skip(first.saturating_sub(1))
+                .take(last + 1 - first)
+                .collect();
+
+            normalize_lua(&lines.join("\n"))
+        });
+
+    match source {
+        Some(src) => src.into_bytes(),
+        None => func.dump(true),
+    }
+}
+
+/// Hashes what a package declares, so that only changes to the package itself
+/// count rather than formatting or comments in its file. Hooks contribute their
+/// normalized source.
+pub fn hash_definition(pkg: &Package) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(serde_json::to_string(&pkg.package_data.definition()).unwrap_or_default());
+
+    for hook in [
+        &pkg.pre_install,
+        &pkg.post_install,
+        &pkg.build,
+        &pkg.install,
+    ] {
+        match hook {
+            Some(func) => hasher.update(fingerprint(func)),
+            None => hasher.update([0]),
+        }
+    }
+
+    format!("{:x}", hasher.finalize())
+}
+
 pub fn get_packages(lua: &Lua, config: &Config) -> Result<Vec<Package>, String> {
-    let mut packages = Vec::<Package>::new();
+    let mut packages = Vec::<(PathBuf, Package)>::new();
 
     for path in &config.packages {
         let f = match read_to_string(path) {
@@ -97,31 +534,145 @@ pub fn get_packages(lua: &Lua, config: &Config) -> Result<Vec<Package>, String>
             }
         };
 
-        lua.set_app_data(FilePathAppData(path.display().to_string()));
-
-        let pkg: Package = match lua.load(f).eval() {
+        let tables = match load_file(lua, path, f) {
             Ok(t) => t,
-            Err(_) => {
+            Err(e) => {
                 return Err(format!(
-                    "Failed to load package from {}",
-                    &path.as_path().display()
+                    "Failed to load packages from {}: {}",
+                    &path.as_path().display(),
+                    e
                 ));
             }
         };
 
+        if tables.is_empty() {
+            return Err(format!(
+                "No packages declared in {}",
+                &path.as_path().display()
+            ));
+        }
+
+        lua.set_app_data(FilePathAppData(path.display().to_string()));
+
+        let mut pkgs = Vec::<Package>::new();
+
+        for table in &tables {
+            match Package::from_lua(Value::Table(table.clone()), lua) {
+                Ok(pkg) => pkgs.push(pkg),
+                Err(e) => {
+                    return Err(format!(
+                        "Failed to load package from {}: {}",
+                        &path.as_path().display(),
+                        e
+                    ));
+                }
+            }
+        }
+
         lua.remove_app_data::<FilePathAppData>();
 
-        packages.push(pkg);
+        packages.extend(pkgs.into_iter().map(|pkg| (path.clone(), pkg)));
     }
 
-    Ok(packages)
+    sort_by_dependencies(packages)
 }
 
-pub fn get_installed_packages(config: &Config) -> Vec<PackageData> {
-    let json_raw = match read_to_string(config.config_dir.join("installed_packages.json")) {
-        Ok(s) => s,
-        Err(_) => return vec![],
-    };
+#[derive(Clone, Copy, PartialEq)]
+enum Visit {
+    Pending,
+    InProgress,
+    Done,
+}
+
+/// Depth first visit that appends `idx` to `order` after its dependencies
+fn visit(
+    idx: usize,
+    packages: &[(PathBuf, Package)],
+    state: &mut [Visit],
+    stack: &mut Vec<usize>,
+    order: &mut Vec<usize>,
+) -> Result<(), String> {
+    match state[idx] {
+        Visit::Done => return Ok(()),
+        Visit::InProgress => {
+            let start = stack.iter().position(|&i| i == idx).unwrap_or(0);
+            let cycle: Vec<String> = stack[start..]
+                .iter()
+                .chain([&idx])
+                .map(|&i| {
+                    let (path, pkg) = &packages[i];
+                    format!("{} ({})", &pkg.package_data.name, path.display())
+                })
+                .collect();
+
+            return Err(format!("Dependency cycle: {}", cycle.join(" -> ")));
+        }
+        Visit::Pending => {}
+    }
+
+    state[idx] = Visit::InProgress;
+    stack.push(idx);
+
+    for dep in &packages[idx].1.package_data.depends_on {
+        for (i, _) in packages
+            .iter()
+            .enumerate()
+            .filter(|(_, (_, p))| &p.package_data.name == dep)
+        {
+            visit(i, packages, state, stack, order)?;
+        }
+    }
+
+    stack.pop();
+    state[idx] = Visit::Done;
+    order.push(idx);
+
+    Ok(())
+}
+
+/// Orders packages so every package comes after the ones it depends on, keeping
+/// the original order otherwise. Dependencies are matched by name.
+fn sort_by_dependencies(packages: Vec<(PathBuf, Package)>) -> Result<Vec<Package>, String> {
+    // Installed packages are tracked by backend and name, so those have to be unique
+    for (i, (path, pkg)) in packages.iter().enumerate() {
+        if let Some((other, _)) = packages[..i]
+            .iter()
+            .find(|(_, p)| p.package_data.is_same_package(&pkg.package_data))
+        {
+            return Err(format!(
+                "{} [{}] is declared in both {} and {}",
+                &pkg.package_data.name,
+                &pkg.package_data.package_type,
+                other.display(),
+                path.display()
+            ));
+        }
+    }
+
+    for (path, pkg) in &packages {
+        for dep in &pkg.package_data.depends_on {
+            if !packages.iter().any(|(_, p)| &p.package_data.name == dep) {
+                eprintln!(
+                    "WARNING: {} ({}) depends on {}, which is not declared in any package file",
+                    &pkg.package_data.name,
+                    path.display(),
+                    dep
+                );
+            }
+        }
+    }
+
+    let mut state = vec![Visit::Pending; packages.len()];
+    let mut order = Vec::with_capacity(packages.len());
+
+    for idx in 0..packages.len() {
+        visit(idx, &packages, &mut state, &mut Vec::new(), &mut order)?;
+    }
+
+    let mut packages: Vec<Option<Package>> = packages.into_iter().map(|(_, p)| Some(p)).collect();
 
-    serde_json::from_str(&json_raw).unwrap_or_default()
+    Ok(order
+        .into_iter()
+        .filter_map(|idx| packages[idx].take())
+        .collect())
 }
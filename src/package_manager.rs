@@ -1,390 +1,451 @@
-use os_info::{get, Type};
-use std::process::Command;
+use os_info::get;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::thread;
+use std::time::Instant;
 
-use crate::package::{Package, PackageData, PackageType};
+use mlua::Function;
 
-use anyhow::{bail, Context, Result};
+use crate::backend::{Backend, Registry};
+use crate::history::{Entry, HistoryRunner, HookRecord, Operation};
+use crate::package::{Package, PackageData, PackageType};
+use crate::runner::{JobRunner, Runner};
 
-pub fn install(pkg: &Package) -> Result<bool> {
-    if let Some(func) = &pkg.pre_install {
-        println!("Running preinstall script");
-        let _ = func.call::<()>(());
-    }
+use anyhow::{anyhow, bail, Result};
 
-    println!("Installing {}", pkg.package_data.name);
+/// Looks up the backend for `pkg` and makes sure it can run on this machine
+fn backend_for<'a>(
+    registry: &'a Registry,
+    runner: &dyn Runner,
+    pkg: &PackageData,
+) -> Result<&'a dyn Backend> {
+    let backend = registry.get(&pkg.package_type)?;
     let os = get().os_type();
-    let ret_code: Option<i32> = match pkg.package_data.package_type {
-        PackageType::Apt => {
-            if os != Type::Pop && os != Type::Debian && os != Type::Ubuntu {
-                eprintln!("Error: Apt is not supported on non-debian based machines");
-                bail!(
-                    "Invalid os ({}) for apt package: {}",
-                    os,
-                    &pkg.package_data.name
-                );
-            }
-
-            let mut cmd = Command::new("sudo");
-
-            let mut args: Vec<String> = Vec::from(["apt".to_string(), "install".to_string()]);
-
-            if let Some(version) = &pkg.package_data.version {
-                args.push(format!("{}={}", &pkg.package_data.name, version));
-            } else {
-                args.push(pkg.package_data.name.clone());
-            }
-
-            if pkg.package_data.channel.is_some() {
-                eprintln!("WARNING: Channels are not supported for apt packages.");
-                eprintln!("Skipping channel argument");
-            }
 
-            args.push("-y".to_string());
-
-            cmd.args(args);
-
-            let mut child = cmd.spawn().context("Failed to spawn sudo apt child")?;
-            let exit_status = child
-                .wait()
-                .context("Failed to wait for sudo apt child to finish")?;
+    if !backend.is_supported(runner, os) {
+        runner.warn(&format!(
+            "ERROR: {} is not supported on {} machines",
+            pkg.package_type, os
+        ));
+        bail!(
+            "Invalid os ({}) for {} package: {}",
+            os,
+            pkg.package_type,
+            &pkg.name
+        );
+    }
 
-            exit_status.code()
-        }
-        PackageType::Snap => {
-            if os != Type::Ubuntu {
-                eprintln!("ERROR: Snap is not supported on non-ubuntu machines");
-                bail!(
-                    "Invalid os ({}) for snap package: {}",
-                    os,
-                    &pkg.package_data.name
-                );
-            }
+    Ok(backend)
+}
 
-            let mut cmd = Command::new("sudo");
-            let mut args: Vec<String> = Vec::from([
-                "snap".to_string(),
-                "install".to_string(),
-                pkg.package_data.name.clone(),
-            ]);
-            let mut channel_arg: String = "--channel=".to_string();
+/// Runs one of a package's lua hooks, or says it would during a dry run.
+/// Returns how it went if it actually ran.
+fn run_hook(runner: &dyn Runner, name: &str, hook: &Option<Function>) -> Option<HookRecord> {
+    let func = hook.as_ref()?;
 
-            if let Some(version) = &pkg.package_data.version {
-                channel_arg.push_str(format!("{}/", version).as_str());
-            }
+    if runner.is_dry_run() {
+        runner.log(&format!("Would run {} script", name));
+        return None;
+    }
 
-            if let Some(channel) = &pkg.package_data.channel {
-                channel_arg.push_str(channel);
-            } else {
-                channel_arg.push_str("stable");
-            }
+    runner.log(&format!("Running {} script", name));
+    let error = func.call::<()>(()).err().map(|e| e.to_string());
 
-            args.push(channel_arg);
+    if let Some(e) = &error {
+        runner.warn(&format!("WARNING: {} script failed: {}", name, e));
+    }
 
-            cmd.args(args);
+    Some(HookRecord {
+        hook: name.to_string(),
+        success: error.is_none(),
+        error,
+    })
+}
 
-            let mut child = cmd.spawn().context("Failed to spawn sudo snap child")?;
+/// Appends an operation to the history log, unless this is a dry run
+fn record(registry: &Registry, runner: &dyn Runner, entry: Entry) {
+    if registry.runner().is_dry_run() {
+        return;
+    }
 
-            let exit_status = child
-                .wait()
-                .context("Failed to wait for sudo snap child to finish")?;
+    if let Err(e) = registry.history().append(&entry) {
+        runner.warn(&format!("WARNING: {}", e));
+    }
+}
 
-            exit_status.code()
-        }
-        PackageType::Brew => {
-            if os != Type::Macos {
-                eprintln!("ERROR: Brew is not supported on non-mac machines");
-                bail!(
-                    "Invalid os ({}) for brew package: {}",
-                    os,
-                    &pkg.package_data.name
-                );
-            }
+pub fn install(registry: &Registry, pkg: &mut Package) -> Result<bool> {
+    install_with(registry, registry.runner(), pkg)
+}
 
-            let mut cmd = Command::new("brew");
-            let mut args: Vec<String> = Vec::from(["install".to_string()]);
-            let mut version_arg: String = pkg.package_data.name.clone();
+fn install_with(registry: &Registry, runner: &dyn Runner, pkg: &mut Package) -> Result<bool> {
+    let started = Instant::now();
+    let runner = HistoryRunner::new(runner);
+    let mut hooks = Vec::new();
 
-            if let Some(version) = &pkg.package_data.version {
-                version_arg.push_str(format!("@{}", version).as_str());
-            }
+    hooks.extend(run_hook(&runner, "preinstall", &pkg.pre_install));
 
-            args.push(version_arg);
-            cmd.args(args);
+    let result = if pkg.reinstall {
+        runner.log(&format!("Reinstalling {}", pkg.package_data.name));
+        backend_for(registry, &runner, &pkg.package_data)
+            .and_then(|backend| backend.reinstall_package(&runner, pkg))
+    } else {
+        runner.log(&format!("Installing {}", pkg.package_data.name));
+        backend_for(registry, &runner, &pkg.package_data)
+            .and_then(|backend| backend.install_package(&runner, pkg))
+    };
 
-            let mut child = cmd.spawn().context("Failed to spawn brew child")?;
+    if matches!(result, Ok(true)) {
+        hooks.extend(run_hook(&runner, "postinstall", &pkg.post_install));
+    }
 
-            let exit_status = child
-                .wait()
-                .context("Failed to wait for brew child to finish")?;
+    record(
+        registry,
+        &runner,
+        Entry::new(
+            Operation::Install,
+            &pkg.package_data,
+            runner.commands(),
+            hooks,
+            started,
+            &result,
+        ),
+    );
+
+    result
+}
 
-            exit_status.code()
-        }
-        PackageType::Winget => {
-            if os != Type::Windows {
-                eprintln!("ERROR: Brew is not supported on non-windows machines");
-                bail!(
-                    "Invalid os ({}) for winget package: {}",
-                    os,
-                    &pkg.package_data.name
-                );
-            }
+/// Installs `pkgs`, handing packages of the same type to their backend in a
+/// single invocation where it supports that. If a batch fails its packages are
+/// retried one at a time to pinpoint the culprit. Results are in the order of
+/// `pkgs`.
+///
+/// Packages are installed in waves so that nothing is installed together with
+/// or before a package it depends on. Packages whose dependencies failed are
+/// not installed at all.
+pub fn install_many(registry: &Registry, pkgs: &mut [Package]) -> Vec<Result<bool>> {
+    let depends = |i: usize, j: usize| {
+        pkgs[i]
+            .package_data
+            .depends_on
+            .contains(&pkgs[j].package_data.name)
+    };
 
-            let mut cmd = Command::new("winget");
-            let mut args: Vec<String> =
-                Vec::from(["install".to_string(), pkg.package_data.name.clone()]);
+    // A package's wave is one past the latest wave of the packages it depends on.
+    // Cycles are rejected when packages are loaded, the bound is only a safeguard.
+    let mut waves = vec![0usize; pkgs.len()];
 
-            if let Some(version) = &pkg.package_data.version {
-                args.push(format!("--version {}", version));
-            }
+    for _ in 0..pkgs.len() {
+        let mut changed = false;
 
-            if pkg.package_data.channel.is_some() {
-                eprintln!("WARNING: Channels are not supported for winget packages");
-                eprintln!("Skipping channel argument");
+        for i in 0..pkgs.len() {
+            for j in (0..pkgs.len()).filter(|&j| depends(i, j)) {
+                if waves[i] <= waves[j] {
+                    waves[i] = waves[j] + 1;
+                    changed = true;
+                }
             }
-
-            cmd.args(args);
-
-            let mut child = cmd.spawn().context("Failed to spawn brew child")?;
-
-            let exit_status = child
-                .wait()
-                .context("Failed to wait for brew child to finish")?;
-
-            exit_status.code()
         }
-    };
-
-    if let Some(func) = &pkg.post_install {
-        println!("Running postinstall script");
-        let _ = func.call::<()>(());
-    }
 
-    if let Some(ret_code) = ret_code {
-        Ok(ret_code == 0)
-    } else {
-        Ok(false)
+        if !changed {
+            break;
+        }
     }
-}
-
-pub fn uninstall(pkg: &PackageData) -> Result<bool> {
-    println!("Uninstalling {}", pkg.name);
-    let os = get().os_type();
-    let ret_code: Option<i32> = match pkg.package_type {
-        PackageType::Apt => {
-            if os != Type::Pop && os != Type::Debian && os != Type::Ubuntu {
-                eprintln!("Error: Apt is not supported on non-debian based machines");
-                bail!("Invalid os ({}) for apt package: {}", os, &pkg.name);
-            }
 
-            let mut cmd = Command::new("sudo");
+    let mut results: Vec<Option<Result<bool>>> = pkgs.iter().map(|_| None).collect();
+    let mut failed = Vec::<String>::new();
 
-            let mut args: Vec<String> = Vec::from(["apt".to_string(), "remove".to_string()]);
+    for wave in 0..=waves.iter().copied().max().unwrap_or(0) {
+        let mut ready = Vec::<usize>::new();
 
-            if let Some(version) = &pkg.version {
-                args.push(format!("{}={}", &pkg.name, version));
-            } else {
-                args.push(pkg.name.clone());
-            }
+        for i in (0..pkgs.len()).filter(|&i| waves[i] == wave) {
+            let data = &pkgs[i].package_data;
 
-            if pkg.channel.is_some() {
-                eprintln!("WARNING: Channels are not supported for apt packages.");
-                eprintln!("Skipping channel argument");
+            match data.depends_on.iter().find(|d| failed.contains(d)) {
+                Some(dep) => {
+                    results[i] = Some(Err(anyhow!(
+                        "Skipped because its dependency {} failed to install",
+                        dep
+                    )));
+                    failed.push(data.name.clone());
+                }
+                None => ready.push(i),
             }
+        }
 
-            args.push("-y".to_string());
-
-            cmd.args(args);
+        let mut wave_pkgs: Vec<Package> = ready.iter().map(|&i| pkgs[i].clone()).collect();
+        let wave_results = install_wave(registry, &mut wave_pkgs);
 
-            let mut child = cmd.spawn().context("Failed to spawn sudo apt child")?;
-            let exit_status = child
-                .wait()
-                .context("Failed to wait for sudo apt child to finish")?;
+        for ((i, pkg), result) in ready.into_iter().zip(wave_pkgs).zip(wave_results) {
+            if !matches!(result, Ok(true)) {
+                failed.push(pkg.package_data.name.clone());
+            }
 
-            exit_status.code()
+            pkgs[i] = pkg;
+            results[i] = Some(result);
         }
-        PackageType::Snap => {
-            if os != Type::Ubuntu {
-                eprintln!("ERROR: Snap is not supported on non-ubuntu machines");
-                bail!("Invalid os ({}) for snap package: {}", os, &pkg.name);
-            }
+    }
 
-            let mut cmd = Command::new("sudo");
-            let args: Vec<String> =
-                Vec::from(["snap".to_string(), "remove".to_string(), pkg.name.clone()]);
+    results.into_iter().flatten().collect()
+}
 
-            cmd.args(args);
+/// Installs packages that don't depend on each other.
+///
+/// With more than one job, each package type becomes a job of its own and up
+/// to [`Registry::jobs`] of them run at once. Packages of one type are still
+/// installed one after the other since most package managers hold a global lock.
+fn install_wave(registry: &Registry, pkgs: &mut [Package]) -> Vec<Result<bool>> {
+    if registry.jobs() <= 1 {
+        return install_serial(registry, registry.runner(), pkgs);
+    }
 
-            let mut child = cmd.spawn().context("Failed to spawn sudo snap child")?;
+    // Indexes of the packages of each type, in the order the types first appear
+    let mut jobs: Vec<(PackageType, Vec<usize>)> = Vec::new();
 
-            let exit_status = child
-                .wait()
-                .context("Failed to wait for sudo snap child to finish")?;
+    for (idx, pkg) in pkgs.iter().enumerate() {
+        let package_type = &pkg.package_data.package_type;
 
-            exit_status.code()
+        match jobs.iter_mut().find(|(t, _)| t == package_type) {
+            Some((_, idxs)) => idxs.push(idx),
+            None => jobs.push((package_type.clone(), Vec::from([idx]))),
         }
-        PackageType::Brew => {
-            if os != Type::Macos {
-                eprintln!("ERROR: Brew is not supported on non-mac machines");
-                bail!("Invalid os ({}) for brew package: {}", os, &pkg.name);
-            }
-
-            let mut cmd = Command::new("brew");
-            let args: Vec<String> = Vec::from(["uninstall".to_string(), pkg.name.clone()]);
-
-            cmd.args(args);
-
-            let mut child = cmd.spawn().context("Failed to spawn brew child")?;
-
-            let exit_status = child
-                .wait()
-                .context("Failed to wait for brew child to finish")?;
+    }
 
-            exit_status.code()
+    let queue = Mutex::new(jobs.into_iter().collect::<VecDeque<_>>());
+    let done = Mutex::new(Vec::<(usize, Package, Result<bool>)>::new());
+    let workers = registry.jobs().min(queue.lock().unwrap().len());
+    let shared: &[Package] = pkgs;
+
+    thread::scope(|s| {
+        for _ in 0..workers {
+            s.spawn(|| loop {
+                let Some((package_type, idxs)) = queue.lock().unwrap().pop_front() else {
+                    break;
+                };
+
+                let runner = JobRunner::new(registry.runner(), package_type.to_string());
+                let mut job: Vec<Package> = idxs.iter().map(|&i| shared[i].clone()).collect();
+                let results = install_serial(registry, &runner, &mut job);
+                runner.finish();
+
+                let mut done = done.lock().unwrap();
+
+                for ((i, pkg), result) in idxs.into_iter().zip(job).zip(results) {
+                    done.push((i, pkg, result));
+                }
+            });
         }
-        PackageType::Winget => {
-            if os != Type::Windows {
-                eprintln!("ERROR: Brew is not supported on non-windows machines");
-                bail!("Invalid os ({}) for winget package: {}", os, &pkg.name);
-            }
+    });
 
-            let mut cmd = Command::new("winget");
-            let mut args: Vec<String> = Vec::from(["uninstall".to_string(), pkg.name.clone()]);
+    let mut results: Vec<Option<Result<bool>>> = pkgs.iter().map(|_| None).collect();
 
-            if let Some(version) = &pkg.version {
-                args.push(format!("--version {}", version));
-            }
+    // Backends may have recorded what they resolved in the package
+    for (i, pkg, result) in done.into_inner().unwrap() {
+        pkgs[i] = pkg;
+        results[i] = Some(result);
+    }
 
-            if pkg.channel.is_some() {
-                eprintln!("WARNING: Channels are not supported for winget packages");
-                eprintln!("Skipping channel argument");
-            }
+    results.into_iter().flatten().collect()
+}
 
-            cmd.args(args);
+fn install_serial(
+    registry: &Registry,
+    runner: &dyn Runner,
+    pkgs: &mut [Package],
+) -> Vec<Result<bool>> {
+    let os = get().os_type();
 
-            let mut child = cmd.spawn().context("Failed to spawn brew child")?;
+    // Indexes of the packages that can be batched, grouped by type. Reinstalls
+    // need their backend's own command so they are never batched.
+    let mut groups: Vec<(PackageType, Vec<usize>)> = Vec::new();
 
-            let exit_status = child
-                .wait()
-                .context("Failed to wait for brew child to finish")?;
+    for (idx, pkg) in pkgs.iter().enumerate() {
+        let data = &pkg.package_data;
+        let batchable = !pkg.reinstall
+            && registry
+                .get(&data.package_type)
+                .is_ok_and(|b| b.is_supported(runner, os) && b.can_batch(data));
 
-            exit_status.code()
+        if !batchable {
+            continue;
         }
-    };
-
-    if let Some(ret_code) = ret_code {
-        Ok(ret_code == 0)
-    } else {
-        Ok(false)
-    }
-}
 
-pub fn update(pkg: &PackageData) -> Result<bool> {
-    println!("Updating {}", pkg.name);
-
-    if pkg.version.is_some() {
-        bail!("Cannot update version locked package");
+        match groups.iter_mut().find(|(t, _)| t == &data.package_type) {
+            Some((_, idxs)) => idxs.push(idx),
+            None => groups.push((data.package_type.clone(), Vec::from([idx]))),
+        }
     }
 
-    let os = get().os_type();
-    let ret_code: Option<i32> = match pkg.package_type {
-        PackageType::Apt => {
-            if os != Type::Pop && os != Type::Debian && os != Type::Ubuntu {
-                eprintln!("Error: Apt is not supported on non-debian based machines");
-                bail!("Invalid os ({}) for apt package: {}", os, &pkg.name);
-            }
-
-            let mut cmd = Command::new("sudo");
+    let mut results: Vec<Option<Result<bool>>> = pkgs.iter().map(|_| None).collect();
 
-            let args: Vec<String> = Vec::from([
-                "apt".to_string(),
-                "install".to_string(),
-                "--only-upgrade".to_string(),
-                pkg.name.clone(),
-                "-y".to_string(),
-            ]);
+    for idx in 0..pkgs.len() {
+        if results[idx].is_some() {
+            continue;
+        }
 
-            if pkg.channel.is_some() {
-                eprintln!("WARNING: Channels are not supported for apt packages.");
-                eprintln!("Skipping channel argument");
+        match groups
+            .iter()
+            .find(|(_, idxs)| idxs.len() > 1 && idxs.contains(&idx))
+        {
+            Some((package_type, idxs)) => {
+                for (i, result) in install_batch(registry, runner, package_type, pkgs, idxs) {
+                    results[i] = Some(result);
+                }
             }
-
-            cmd.args(args);
-
-            let mut child = cmd.spawn().context("Failed to spawn sudo apt child")?;
-            let exit_status = child
-                .wait()
-                .context("Failed to wait for sudo apt child to finish")?;
-
-            exit_status.code()
+            None => results[idx] = Some(install_with(registry, runner, &mut pkgs[idx])),
         }
-        PackageType::Snap => {
-            if os != Type::Ubuntu {
-                eprintln!("ERROR: Snap is not supported on non-ubuntu machines");
-                bail!("Invalid os ({}) for snap package: {}", os, &pkg.name);
-            }
-
-            let mut cmd = Command::new("sudo");
-            let args: Vec<String> =
-                Vec::from(["snap".to_string(), "refresh".to_string(), pkg.name.clone()]);
-
-            cmd.args(args);
-
-            let mut child = cmd.spawn().context("Failed to spawn sudo snap child")?;
+    }
 
-            let exit_status = child
-                .wait()
-                .context("Failed to wait for sudo snap child to finish")?;
+    results.into_iter().flatten().collect()
+}
 
-            exit_status.code()
+fn install_batch(
+    registry: &Registry,
+    runner: &dyn Runner,
+    package_type: &PackageType,
+    pkgs: &mut [Package],
+    idxs: &[usize],
+) -> Vec<(usize, Result<bool>)> {
+    let backend = match registry.get(package_type) {
+        Ok(b) => b,
+        Err(e) => {
+            let msg = e.to_string();
+            return idxs
+                .iter()
+                .map(|&i| (i, Err(anyhow!(msg.clone()))))
+                .collect();
         }
-        PackageType::Brew => {
-            if os != Type::Macos {
-                eprintln!("ERROR: Brew is not supported on non-mac machines");
-                bail!("Invalid os ({}) for brew package: {}", os, &pkg.name);
-            }
+    };
 
-            let mut cmd = Command::new("brew");
-            let args: Vec<String> = Vec::from(["upgrade".to_string(), pkg.name.clone()]);
+    let started = Instant::now();
+    let mut hooks: Vec<Vec<HookRecord>> = idxs
+        .iter()
+        .map(|&i| {
+            run_hook(runner, "preinstall", &pkgs[i].pre_install)
+                .into_iter()
+                .collect()
+        })
+        .collect();
+
+    let names: Vec<&str> = idxs
+        .iter()
+        .map(|&i| pkgs[i].package_data.name.as_str())
+        .collect();
+    runner.log(&format!("Installing {}", names.join(", ")));
+
+    let data: Vec<&PackageData> = idxs.iter().map(|&i| &pkgs[i].package_data).collect();
+    let batch_runner = HistoryRunner::new(runner);
+
+    let batch = match backend.install_batch(&batch_runner, &data) {
+        Ok(b) => b,
+        Err(e) => {
+            runner.warn(&format!("ERROR: {}", e));
+            false
+        }
+    };
 
-            cmd.args(args);
+    if !batch {
+        runner.warn(&format!(
+            "WARNING: Installing {} packages together failed. Retrying one at a time",
+            package_type
+        ));
+    }
 
-            let mut child = cmd.spawn().context("Failed to spawn brew child")?;
+    idxs.iter()
+        .zip(hooks.iter_mut())
+        .map(|(&i, hooks)| {
+            let pkg = &mut pkgs[i];
+            let retry_runner = HistoryRunner::new(runner);
 
-            let exit_status = child
-                .wait()
-                .context("Failed to wait for brew child to finish")?;
+            let result = if batch {
+                Ok(true)
+            } else {
+                retry_runner.log(&format!("Installing {}", pkg.package_data.name));
+                backend.install_package(&retry_runner, pkg)
+            };
 
-            exit_status.code()
-        }
-        PackageType::Winget => {
-            if os != Type::Windows {
-                eprintln!("ERROR: Winget is not supported on non-windows machines");
-                bail!("Invalid os ({}) for winget package: {}", os, &pkg.name);
+            if matches!(result, Ok(true)) {
+                hooks.extend(run_hook(runner, "postinstall", &pkg.post_install));
             }
 
-            let mut cmd = Command::new("winget");
-            let args: Vec<String> = Vec::from(["upgrade".to_string(), pkg.name.clone()]);
-
-            if pkg.channel.is_some() {
-                eprintln!("WARNING: Channels are not supported for winget packages");
-                eprintln!("Skipping channel argument");
-            }
+            // Every package of the batch shares the batch command
+            let mut commands = batch_runner.commands();
+            commands.extend(retry_runner.commands());
+
+            record(
+                registry,
+                runner,
+                Entry::new(
+                    Operation::Install,
+                    &pkg.package_data,
+                    commands,
+                    std::mem::take(hooks),
+                    started,
+                    &result,
+                ),
+            );
+
+            (i, result)
+        })
+        .collect()
+}
 
-            cmd.args(args);
+pub fn uninstall(registry: &Registry, pkg: &PackageData) -> Result<bool> {
+    let started = Instant::now();
+    let runner = HistoryRunner::new(registry.runner());
+    runner.log(&format!("Uninstalling {}", pkg.name));
+
+    let result =
+        backend_for(registry, &runner, pkg).and_then(|backend| backend.uninstall(&runner, pkg));
+
+    record(
+        registry,
+        &runner,
+        Entry::new(
+            Operation::Uninstall,
+            pkg,
+            runner.commands(),
+            vec![],
+            started,
+            &result,
+        ),
+    );
+
+    result
+}
 
-            let mut child = cmd.spawn().context("Failed to spawn winget child")?;
+pub fn update(registry: &Registry, pkg: &mut Package) -> Result<bool> {
+    registry
+        .runner()
+        .log(&format!("Updating {}", pkg.package_data.name));
 
-            let exit_status = child
-                .wait()
-                .context("Failed to wait for winget child to finish")?;
+    if pkg.package_data.version.is_some() {
+        bail!("Cannot update version locked package");
+    }
 
-            exit_status.code()
-        }
-    };
+    let started = Instant::now();
+    let runner = HistoryRunner::new(registry.runner());
+    let result = backend_for(registry, &runner, &pkg.package_data)
+        .and_then(|backend| backend.update_package(&runner, pkg));
+
+    record(
+        registry,
+        &runner,
+        Entry::new(
+            Operation::Update,
+            &pkg.package_data,
+            runner.commands(),
+            vec![],
+            started,
+            &result,
+        ),
+    );
+
+    result
+}
 
-    if let Some(ret_code) = ret_code {
-        Ok(ret_code == 0)
-    } else {
-        Ok(false)
-    }
+/// Asks the backend which version of `pkg` is currently installed
+pub fn query(registry: &Registry, pkg: &PackageData) -> Result<Option<String>> {
+    registry
+        .get(&pkg.package_type)?
+        .query(registry.runner(), pkg)
 }
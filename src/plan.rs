@@ -0,0 +1,201 @@
+use std::fmt::Display;
+
+use crate::package::{Package, PackageData};
+
+/// A single step nexus intends to take for a package
+pub enum Action {
+    /// Declared but not installed yet
+    Install(Package),
+    /// Installed, but the local file it was installed from has changed since
+    Reinstall(Package),
+    /// Declared and already installed from the same definition
+    Skip(PackageData),
+    /// Installed but no longer declared
+    Remove(PackageData),
+    /// Installed and no longer declared, but kept since the named declared
+    /// package depends on it
+    Needed(PackageData, String),
+    /// Installed, but its definition changed since. Carries the fields that
+    /// differ from what was installed, which is empty if only hooks changed.
+    Modify(Package, Vec<String>),
+    /// Installed and not pinned to a version. Carries the package's lua
+    /// functions when it is still declared.
+    Upgrade(Package),
+    /// Installed but pinned to a version, so it is left alone by updates
+    Pinned(PackageData),
+}
+
+impl Display for Action {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Install(pkg) => write!(f, "install {}", describe(&pkg.package_data)),
+            Self::Reinstall(pkg) => write!(
+                f,
+                "reinstall {} (source file changed)",
+                describe(&pkg.package_data)
+            ),
+            Self::Skip(data) => write!(f, "skip {} (unchanged)", describe(data)),
+            Self::Remove(data) => write!(f, "remove {}", describe(data)),
+            Self::Needed(data, by) => {
+                write!(f, "keep {} (needed by {})", describe(data), by)
+            }
+            Self::Modify(pkg, fields) if fields.is_empty() => {
+                write!(f, "modify {} (hooks changed)", describe(&pkg.package_data))
+            }
+            Self::Modify(pkg, fields) => write!(
+                f,
+                "modify {} ({} changed)",
+                describe(&pkg.package_data),
+                fields.join(", ")
+            ),
+            Self::Upgrade(pkg) => write!(f, "upgrade {}", describe(&pkg.package_data)),
+            Self::Pinned(data) => write!(f, "skip {} (pinned version)", describe(data)),
+        }
+    }
+}
+
+fn describe(data: &PackageData) -> String {
+    format!("{} [{}]", &data.name, &data.package_type)
+}
+
+/// The declared package `data` was installed as, if it is still declared
+fn declared<'a>(pkgs: &'a [Package], data: &PackageData) -> Option<&'a Package> {
+    pkgs.iter().find(|p| p.package_data.is_same_package(data))
+}
+
+/// Pairs installed data with the lua functions of its declaration, if it is
+/// still declared
+fn with_functions(pkgs: &[Package], data: &PackageData) -> Package {
+    match declared(pkgs, data) {
+        Some(declared) => Package {
+            package_data: data.clone(),
+            ..declared.clone()
+        },
+        None => Package::from(data.clone()),
+    }
+}
+
+/// Removes `data` unless a declared package depends on it
+fn remove(pkgs: &[Package], data: &PackageData) -> Action {
+    match pkgs
+        .iter()
+        .find(|p| p.package_data.depends_on.contains(&data.name))
+    {
+        Some(dependent) => Action::Needed(data.clone(), dependent.package_data.name.clone()),
+        None => Action::Remove(data.clone()),
+    }
+}
+
+/// What `nexus install` would do for every declared package
+pub fn install(pkgs: &[Package], installed: &[PackageData]) -> Vec<Action> {
+    pkgs.iter()
+        .map(|pkg| {
+            let declared = &pkg.package_data;
+
+            match installed.iter().find(|p| p.is_same_package(declared)) {
+                None => Action::Install(pkg.clone()),
+                Some(p) if !p.same_definition(declared) => {
+                    Action::Modify(pkg.clone(), p.changed_fields(declared))
+                }
+                Some(p) if p.source_hash != declared.source_hash => Action::Reinstall(pkg.clone()),
+                Some(_) => Action::Skip(declared.clone()),
+            }
+        })
+        .collect()
+}
+
+/// What `nexus purge` would do for every installed package that is no longer
+/// declared. Packages are removed in the reverse of the order they were
+/// installed in, so dependents go before their dependencies.
+pub fn purge(pkgs: &[Package], installed: &[PackageData]) -> Vec<Action> {
+    installed
+        .iter()
+        .rev()
+        .filter(|data| declared(pkgs, data).is_none())
+        .map(|data| remove(pkgs, data))
+        .collect()
+}
+
+/// What `nexus update` would do for every installed package
+pub fn update(pkgs: &[Package], installed: &[PackageData]) -> Vec<Action> {
+    installed
+        .iter()
+        .map(|data| {
+            if data.version.is_some() {
+                return Action::Pinned(data.clone());
+            }
+
+            Action::Upgrade(with_functions(pkgs, data))
+        })
+        .collect()
+}
+
+/// Everything `nexus sync` would do to bring the installed packages in line
+/// with the declared ones, in the order it is done: removals, additions,
+/// changed definitions and finally upgrades of the packages left untouched.
+pub fn sync(pkgs: &[Package], installed: &[PackageData]) -> Vec<Action> {
+    let removals = purge(pkgs, installed);
+    let mut additions = Vec::new();
+    let mut changes = Vec::new();
+    let mut untouched = Vec::new();
+
+    for action in install(pkgs, installed) {
+        match action {
+            Action::Install(_) => additions.push(action),
+            Action::Modify(..) | Action::Reinstall(_) => changes.push(action),
+            // Upgrades are planned from the installed data, which still
+            // carries resolved fields such as a git commit
+            Action::Skip(data) => {
+                untouched.extend(installed.iter().find(|i| i.is_same_package(&data)).cloned())
+            }
+            _ => {}
+        }
+    }
+
+    let upgrades = update(pkgs, &untouched);
+
+    removals
+        .into_iter()
+        .chain(additions)
+        .chain(changes)
+        .chain(upgrades)
+        .collect()
+}
+
+/// The version a package was at when it was recorded
+fn installed_version(data: &PackageData) -> Option<&String> {
+    data.installed.as_ref().and_then(|i| i.version.as_ref())
+}
+
+/// What `nexus rollback` would do to bring the installed packages back to
+/// `target`, an earlier generation of them: packages it didn't have are
+/// removed, newest first, then its packages are restored in the order they
+/// were installed in.
+pub fn rollback(
+    pkgs: &[Package],
+    installed: &[PackageData],
+    target: &[PackageData],
+) -> Vec<Action> {
+    let removals = installed
+        .iter()
+        .rev()
+        .filter(|data| !target.iter().any(|t| t.is_same_package(data)))
+        .map(|data| Action::Remove(data.clone()));
+
+    let restores = target.iter().map(|data| {
+        let pkg = with_functions(pkgs, data);
+
+        match installed.iter().find(|i| i.is_same_package(data)) {
+            None => Action::Install(pkg),
+            Some(current) if current.hash != data.hash => {
+                Action::Modify(pkg, current.changed_fields(data))
+            }
+            Some(current) if installed_version(current) != installed_version(data) => {
+                Action::Modify(pkg, vec!["installed version".to_string()])
+            }
+            Some(_) => Action::Skip(data.clone()),
+        }
+    });
+
+    removals.chain(restores).collect()
+}
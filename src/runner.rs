@@ -0,0 +1,274 @@
+use std::collections::HashMap;
+use std::io::Write;
+use std::process::Command;
+use std::sync::{Arc, Mutex};
+
+use anyhow::{Context, Result};
+
+/// Executes the commands backends build.
+///
+/// Backends never spawn processes themselves, which lets nexus swap in a
+/// [`DryRunner`] for `--dry-run` and a [`RecordingRunner`] in tests.
+pub trait Runner: Send + Sync {
+    /// Runs `cmd` to completion and reports whether it exited successfully
+    fn run(&self, cmd: Command) -> Result<bool>;
+
+    /// Runs `cmd` to completion and returns its exit code, or `None` if it
+    /// failed without one (or the runner can't tell)
+    fn run_with_code(&self, cmd: Command) -> Result<Option<i32>> {
+        Ok(if self.run(cmd)? { Some(0) } else { None })
+    }
+
+    /// Runs `cmd` to completion and returns its trimmed stdout if it exited successfully
+    fn output(&self, cmd: Command) -> Result<Option<String>>;
+
+    /// Runs `cmd` to completion and returns its exit code along with
+    /// everything it printed, instead of letting it print directly
+    fn run_captured(&self, cmd: Command) -> Result<(Option<i32>, String)> {
+        Ok((self.run_with_code(cmd)?, String::new()))
+    }
+
+    /// Prints a status message about what nexus is doing
+    fn log(&self, msg: &str) {
+        println!("{}", msg);
+    }
+
+    /// Prints a warning or error, which callers prefix themselves
+    fn warn(&self, msg: &str) {
+        eprintln!("{}", msg);
+    }
+
+    /// Whether side effects that don't go through the runner (hooks, file
+    /// writes) should be skipped as well
+    fn is_dry_run(&self) -> bool {
+        false
+    }
+}
+
+/// Renders `cmd` the way it would be typed into a shell
+pub fn display_command(cmd: &Command) -> String {
+    argv(cmd)
+        .into_iter()
+        .map(|arg| {
+            if arg.contains(char::is_whitespace) {
+                format!("'{}'", arg)
+            } else {
+                arg
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// The program followed by its arguments
+pub fn argv(cmd: &Command) -> Vec<String> {
+    std::iter::once(cmd.get_program())
+        .chain(cmd.get_args())
+        .map(|arg| arg.to_string_lossy().to_string())
+        .collect()
+}
+
+/// Spawns real processes
+pub struct SystemRunner;
+
+impl Runner for SystemRunner {
+    fn run(&self, cmd: Command) -> Result<bool> {
+        Ok(self.run_with_code(cmd)? == Some(0))
+    }
+
+    fn run_with_code(&self, mut cmd: Command) -> Result<Option<i32>> {
+        let program = cmd.get_program().to_string_lossy().to_string();
+
+        let mut child = cmd
+            .spawn()
+            .with_context(|| format!("Failed to spawn {} child", program))?;
+
+        let exit_status = child
+            .wait()
+            .with_context(|| format!("Failed to wait for {} child to finish", program))?;
+
+        Ok(exit_status.code())
+    }
+
+    fn output(&self, mut cmd: Command) -> Result<Option<String>> {
+        let program = cmd.get_program().to_string_lossy().to_string();
+
+        let output = cmd
+            .output()
+            .with_context(|| format!("Failed to run {}", program))?;
+
+        if !output.status.success() {
+            return Ok(None);
+        }
+
+        Ok(Some(
+            String::from_utf8_lossy(&output.stdout).trim().to_string(),
+        ))
+    }
+
+    fn run_captured(&self, mut cmd: Command) -> Result<(Option<i32>, String)> {
+        let program = cmd.get_program().to_string_lossy().to_string();
+
+        let output = cmd
+            .output()
+            .with_context(|| format!("Failed to run {}", program))?;
+
+        let printed = String::from_utf8_lossy(&output.stdout).to_string()
+            + &String::from_utf8_lossy(&output.stderr);
+
+        Ok((output.status.code(), printed))
+    }
+}
+
+/// Prints the commands that would be run and treats them as successful.
+/// Read-only queries still go through to the system.
+pub struct DryRunner;
+
+impl Runner for DryRunner {
+    fn run(&self, cmd: Command) -> Result<bool> {
+        println!("Would run: {}", display_command(&cmd));
+
+        Ok(true)
+    }
+
+    fn output(&self, cmd: Command) -> Result<Option<String>> {
+        SystemRunner.output(cmd)
+    }
+
+    fn run_captured(&self, cmd: Command) -> Result<(Option<i32>, String)> {
+        Ok((Some(0), format!("Would run: {}", display_command(&cmd))))
+    }
+
+    fn is_dry_run(&self) -> bool {
+        true
+    }
+}
+
+/// Runs the commands of one job during a parallel install. Status messages and
+/// everything the commands print are held back and written out in one block,
+/// each line prefixed with the job's name, when [`JobRunner::finish`] is called.
+pub struct JobRunner<'a> {
+    inner: &'a dyn Runner,
+    prefix: String,
+    lines: Mutex<Vec<String>>,
+}
+
+impl<'a> JobRunner<'a> {
+    pub fn new(inner: &'a dyn Runner, prefix: String) -> Self {
+        Self {
+            inner,
+            prefix,
+            lines: Mutex::new(Vec::new()),
+        }
+    }
+
+    fn push(&self, text: &str) {
+        self.lines
+            .lock()
+            .unwrap()
+            .extend(text.lines().map(|l| l.to_string()));
+    }
+
+    /// Prints everything the job logged
+    pub fn finish(self) {
+        let lines = self.lines.into_inner().unwrap();
+        let mut stdout = std::io::stdout().lock();
+
+        for line in lines {
+            let _ = writeln!(stdout, "[{}] {}", self.prefix, line);
+        }
+    }
+}
+
+impl Runner for JobRunner<'_> {
+    fn run(&self, cmd: Command) -> Result<bool> {
+        Ok(self.run_with_code(cmd)? == Some(0))
+    }
+
+    fn run_with_code(&self, cmd: Command) -> Result<Option<i32>> {
+        let (code, printed) = self.inner.run_captured(cmd)?;
+        self.push(&printed);
+
+        Ok(code)
+    }
+
+    fn output(&self, cmd: Command) -> Result<Option<String>> {
+        self.inner.output(cmd)
+    }
+
+    fn log(&self, msg: &str) {
+        self.push(msg);
+    }
+
+    fn warn(&self, msg: &str) {
+        self.push(msg);
+    }
+
+    fn is_dry_run(&self) -> bool {
+        self.inner.is_dry_run()
+    }
+}
+
+/// Records the argv of every command instead of running it. Clones share the
+/// same recording so a test can keep a handle after giving one to a registry.
+/// Queries print nothing unless given output through [`RecordingRunner::set_output`].
+#[derive(Clone, Default)]
+pub struct RecordingRunner {
+    calls: Arc<Mutex<Vec<Vec<String>>>>,
+    queries: Arc<Mutex<Vec<Vec<String>>>>,
+    outputs: Arc<Mutex<HashMap<String, String>>>,
+    fail: bool,
+}
+
+impl RecordingRunner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A runner whose commands all exit unsuccessfully
+    pub fn failing() -> Self {
+        Self {
+            fail: true,
+            ..Self::default()
+        }
+    }
+
+    /// Commands that were run
+    pub fn calls(&self) -> Vec<Vec<String>> {
+        self.calls.lock().unwrap().clone()
+    }
+
+    /// Commands whose output was asked for, which never change anything
+    pub fn queries(&self) -> Vec<Vec<String>> {
+        self.queries.lock().unwrap().clone()
+    }
+
+    /// Makes every query of `program` succeed and print `output` from now on
+    pub fn set_output(&self, program: &str, output: &str) {
+        self.outputs
+            .lock()
+            .unwrap()
+            .insert(program.to_string(), output.to_string());
+    }
+}
+
+impl Runner for RecordingRunner {
+    fn run(&self, cmd: Command) -> Result<bool> {
+        Ok(self.run_with_code(cmd)? == Some(0))
+    }
+
+    fn run_with_code(&self, cmd: Command) -> Result<Option<i32>> {
+        self.calls.lock().unwrap().push(argv(&cmd));
+
+        Ok(Some(if self.fail { 1 } else { 0 }))
+    }
+
+    fn output(&self, cmd: Command) -> Result<Option<String>> {
+        let argv = argv(&cmd);
+        let output = self.outputs.lock().unwrap().get(&argv[0]).cloned();
+
+        self.queries.lock().unwrap().push(argv);
+
+        Ok(output)
+    }
+}
@@ -0,0 +1,234 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use std::fs::{self, read_to_string, File};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+use crate::config::Config;
+use crate::package::PackageData;
+
+/// Version of the state file layout. Bump it whenever a change needs existing
+/// files to be migrated, and add the step to [`migrate`].
+pub const SCHEMA: u64 = 1;
+
+#[derive(Serialize, Deserialize)]
+struct StateFile {
+    schema: u64,
+    packages: Vec<PackageData>,
+}
+
+/// Brings a state file written by any earlier version of nexus up to [`SCHEMA`]
+fn migrate(mut json: Value) -> Result<Value, String> {
+    loop {
+        let schema = match &json {
+            // Before the envelope, the file was a bare list of packages
+            Value::Array(_) => 0,
+            Value::Object(o) => o
+                .get("schema")
+                .and_then(Value::as_u64)
+                .ok_or("Missing schema version")?,
+            _ => return Err("Expected a list of packages or a versioned state".to_string()),
+        };
+
+        json = match schema {
+            0 => serde_json::json!({ "schema": 1, "packages": json }),
+            SCHEMA => return Ok(json),
+            newer => {
+                return Err(format!(
+                    "State schema {} is newer than the {} this version of nexus understands",
+                    newer, SCHEMA
+                ))
+            }
+        };
+    }
+}
+
+/// Where the packages nexus has installed are recorded
+pub fn path(config: &Config) -> PathBuf {
+    config.config_dir.join("installed_packages.json")
+}
+
+/// The state as it was before the last save
+pub fn backup_path(config: &Config) -> PathBuf {
+    config.config_dir.join("installed_packages.json.bak")
+}
+
+/// Parses a state file, migrating it first if needed. `hint` is appended to
+/// errors about a corrupted file.
+fn parse(path: &Path, json_raw: &str, hint: &str) -> Result<Vec<PackageData>, String> {
+    let corrupted = |e: String| format!("Failed to parse {}: {}{}", path.display(), e, hint);
+
+    let json: Value = serde_json::from_str(json_raw).map_err(|e| corrupted(e.to_string()))?;
+    let json = migrate(json).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let state: StateFile = serde_json::from_value(json).map_err(|e| corrupted(e.to_string()))?;
+
+    Ok(state.packages)
+}
+
+/// Reads the installed packages. A missing file means nothing was installed
+/// yet, but a file that can't be read or parsed is an error rather than an
+/// empty state, since continuing would make nexus forget what it installed.
+pub fn load(config: &Config) -> Result<Vec<PackageData>, String> {
+    let path = path(config);
+
+    let json_raw = match read_to_string(&path) {
+        Ok(s) => s,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+    };
+
+    let hint = format!(
+        ". The previous state may be recovered from {}",
+        backup_path(config).display()
+    );
+
+    parse(&path, &json_raw, &hint)
+}
+
+/// Writes the installed packages, keeping the old ones as a backup first
+pub fn save(config: &Config, packages: &[PackageData]) -> Result<(), String> {
+    backup(config)?;
+    write(config, packages)
+}
+
+/// Copies the current state to the backup file, if there is a state yet
+pub fn backup(config: &Config) -> Result<(), String> {
+    let path = path(config);
+
+    if !path.exists() {
+        return Ok(());
+    }
+
+    let backup = backup_path(config);
+
+    fs::copy(&path, &backup)
+        .and_then(|_| File::open(&backup)?.sync_all())
+        .map_err(|e| format!("Failed to back up {}: {}", path.display(), e))?;
+
+    Ok(())
+}
+
+/// Writes the installed packages without ever leaving a partial file behind:
+/// the new state is written and synced to a temporary file which then replaces
+/// the old one. Leaves the backup alone, for runs that save as they go after
+/// taking it once with [`backup`].
+pub fn write(config: &Config, packages: &[PackageData]) -> Result<(), String> {
+    let path = path(config);
+    let tmp = config.config_dir.join("installed_packages.json.tmp");
+
+    let json = serialize(packages)?;
+
+    write_synced(&tmp, json.as_bytes())
+        .map_err(|e| format!("Failed to write {}: {}", tmp.display(), e))?;
+
+    fs::rename(&tmp, &path).map_err(|e| format!("Failed to replace {}: {}", path.display(), e))?;
+
+    sync_dir(&config.config_dir);
+
+    Ok(())
+}
+
+fn serialize(packages: &[PackageData]) -> Result<String, String> {
+    let state = StateFile {
+        schema: SCHEMA,
+        packages: packages.to_vec(),
+    };
+
+    serde_json::to_string_pretty(&state)
+        .map_err(|_| "Failed to serialize installed packages".to_string())
+}
+
+/// Where every generation of the state is kept, one file per mutating run
+pub fn generations_dir(config: &Config) -> PathBuf {
+    config.config_dir.join("generations")
+}
+
+fn generation_path(config: &Config, generation: u64) -> PathBuf {
+    generations_dir(config).join(format!("{}.json", generation))
+}
+
+/// Numbers of the recorded generations, oldest first
+pub fn generations(config: &Config) -> Result<Vec<u64>, String> {
+    let dir = generations_dir(config);
+
+    let entries = match fs::read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(format!("Failed to read {}: {}", dir.display(), e)),
+    };
+
+    let mut generations: Vec<u64> = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            entry
+                .file_name()
+                .to_str()?
+                .strip_suffix(".json")?
+                .parse()
+                .ok()
+        })
+        .collect();
+
+    generations.sort_unstable();
+
+    Ok(generations)
+}
+
+/// The installed packages as they were recorded in `generation`
+pub fn load_generation(config: &Config, generation: u64) -> Result<Vec<PackageData>, String> {
+    let path = generation_path(config, generation);
+
+    let json_raw = match read_to_string(&path) {
+        Ok(s) => s,
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            return Err(format!("Generation {} does not exist", generation))
+        }
+        Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+    };
+
+    parse(&path, &json_raw, "")
+}
+
+/// Records `packages` as a new generation, unless the latest one already holds
+/// exactly them. Returns the number of the new generation.
+pub fn save_generation(config: &Config, packages: &[PackageData]) -> Result<Option<u64>, String> {
+    let latest = generations(config)?.last().copied();
+    let json = serialize(packages)?;
+
+    if let Some(latest) = latest {
+        if read_to_string(generation_path(config, latest)).is_ok_and(|prev| prev == json) {
+            return Ok(None);
+        }
+    }
+
+    let dir = generations_dir(config);
+    let generation = latest.map_or(1, |n| n + 1);
+    let path = generation_path(config, generation);
+    let tmp = dir.join(format!("{}.json.tmp", generation));
+
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+
+    write_synced(&tmp, json.as_bytes())
+        .map_err(|e| format!("Failed to write {}: {}", tmp.display(), e))?;
+    fs::rename(&tmp, &path).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+
+    sync_dir(&dir);
+
+    Ok(Some(generation))
+}
+
+fn write_synced(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let mut file = File::create(path)?;
+
+    file.write_all(contents)?;
+    file.sync_all()
+}
+
+/// Makes the rename itself durable. Not every platform can sync a directory,
+/// in which case the rename is as durable as the filesystem makes it.
+fn sync_dir(dir: &Path) {
+    if let Ok(dir) = File::open(dir) {
+        let _ = dir.sync_all();
+    }
+}
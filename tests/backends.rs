@@ -0,0 +1,1066 @@
+mod common;
+
+use common::{argv, data, Env};
+use nexus::backend::Registry;
+use nexus::package::{self, Package, PackageData, PackageType};
+use nexus::runner::{RecordingRunner, SystemRunner};
+use serde_json::json;
+use std::fs::{create_dir_all, read_dir, read_to_string, write};
+use std::path::Path;
+use std::process::Command;
+
+fn install(package_type: PackageType, pkg: serde_json::Value) -> Vec<Vec<String>> {
+    let env = Env::new();
+    let runner = RecordingRunner::new();
+    let registry = env.registry(&runner);
+
+    let ok = registry
+        .get(&package_type)
+        .unwrap()
+        .install(registry.runner(), &data(pkg))
+        .unwrap();
+
+    assert!(ok);
+    runner.calls()
+}
+
+fn uninstall(package_type: PackageType, pkg: serde_json::Value) -> Vec<Vec<String>> {
+    let env = Env::new();
+    let runner = RecordingRunner::new();
+    let registry = env.registry(&runner);
+
+    registry
+        .get(&package_type)
+        .unwrap()
+        .uninstall(registry.runner(), &data(pkg))
+        .unwrap();
+
+    runner.calls()
+}
+
+fn update(package_type: PackageType, pkg: serde_json::Value) -> Vec<Vec<String>> {
+    let env = Env::new();
+    let runner = RecordingRunner::new();
+    let registry = env.registry(&runner);
+
+    registry
+        .get(&package_type)
+        .unwrap()
+        .update(registry.runner(), &data(pkg))
+        .unwrap();
+
+    runner.calls()
+}
+
+/// Goes through `reinstall_package` like installs of changed packages do
+fn reinstall(package_type: PackageType, pkg: serde_json::Value) -> Vec<Vec<String>> {
+    let env = Env::new();
+    let runner = RecordingRunner::new();
+    let registry = env.registry(&runner);
+
+    let ok = registry
+        .get(&package_type)
+        .unwrap()
+        .reinstall_package(registry.runner(), &mut Package::from(data(pkg)))
+        .unwrap();
+
+    assert!(ok);
+    runner.calls()
+}
+
+#[test]
+fn apt() {
+    let pkg = json!({ "name": "curl", "package_type": "apt", "version": "7.88" });
+
+    assert_eq!(
+        install(PackageType::Apt, pkg.clone()),
+        [argv(&["sudo", "apt", "install", "curl=7.88", "-y"])]
+    );
+    assert_eq!(
+        uninstall(PackageType::Apt, pkg.clone()),
+        [argv(&["sudo", "apt", "remove", "curl=7.88", "-y"])]
+    );
+    // Lowering the declared version reinstalls a version older than the installed one
+    assert_eq!(
+        reinstall(PackageType::Apt, pkg),
+        [argv(&[
+            "sudo",
+            "apt",
+            "install",
+            "--allow-downgrades",
+            "curl=7.88",
+            "-y"
+        ])]
+    );
+    assert_eq!(
+        update(
+            PackageType::Apt,
+            json!({ "name": "curl", "package_type": "apt" })
+        ),
+        [argv(&[
+            "sudo",
+            "apt",
+            "install",
+            "--only-upgrade",
+            "curl",
+            "-y"
+        ])]
+    );
+}
+
+#[test]
+fn apt_local_file() {
+    let pkg = json!({
+        "name": "vendor-tool",
+        "package_type": "apt",
+        "source": "/opt/debs/vendor-tool.deb",
+    });
+
+    assert_eq!(
+        install(PackageType::Apt, pkg),
+        [argv(&[
+            "sudo",
+            "apt",
+            "install",
+            "/opt/debs/vendor-tool.deb",
+            "-y"
+        ])]
+    );
+}
+
+#[test]
+fn snap() {
+    let pkg = json!({
+        "name": "code",
+        "package_type": "snap",
+        "version": "1.85",
+        "channel": "beta",
+    });
+
+    assert_eq!(
+        install(PackageType::Snap, pkg.clone()),
+        [argv(&[
+            "sudo",
+            "snap",
+            "install",
+            "code",
+            "--channel=1.85/beta"
+        ])]
+    );
+    assert_eq!(
+        uninstall(PackageType::Snap, pkg.clone()),
+        [argv(&["sudo", "snap", "remove", "code"])]
+    );
+    assert_eq!(
+        update(PackageType::Snap, pkg.clone()),
+        [argv(&["sudo", "snap", "refresh", "code"])]
+    );
+    assert_eq!(
+        reinstall(PackageType::Snap, pkg),
+        [argv(&[
+            "sudo",
+            "snap",
+            "refresh",
+            "code",
+            "--channel=1.85/beta"
+        ])]
+    );
+}
+
+#[test]
+fn brew() {
+    let pkg = json!({ "name": "python", "package_type": "brew", "version": "3.12" });
+
+    assert_eq!(
+        install(PackageType::Brew, pkg.clone()),
+        [argv(&["brew", "install", "python@3.12"])]
+    );
+    assert_eq!(
+        uninstall(PackageType::Brew, pkg.clone()),
+        [argv(&["brew", "uninstall", "python"])]
+    );
+    assert_eq!(
+        update(PackageType::Brew, pkg),
+        [argv(&["brew", "upgrade", "python"])]
+    );
+}
+
+#[test]
+fn winget() {
+    let pkg = json!({ "name": "Git.Git", "package_type": "winget", "version": "2.43.0" });
+
+    assert_eq!(
+        install(PackageType::Winget, pkg.clone()),
+        [argv(&[
+            "winget",
+            "install",
+            "Git.Git",
+            "--version",
+            "2.43.0"
+        ])]
+    );
+    assert_eq!(
+        uninstall(PackageType::Winget, pkg.clone()),
+        [argv(&[
+            "winget",
+            "uninstall",
+            "Git.Git",
+            "--version",
+            "2.43.0"
+        ])]
+    );
+    assert_eq!(
+        update(PackageType::Winget, pkg),
+        [argv(&["winget", "upgrade", "Git.Git"])]
+    );
+}
+
+#[test]
+fn pacman() {
+    let pkg = json!({ "name": "git", "package_type": "pacman" });
+
+    assert_eq!(
+        install(PackageType::Pacman, pkg.clone()),
+        [argv(&[
+            "sudo",
+            "pacman",
+            "-S",
+            "--needed",
+            "git",
+            "--noconfirm"
+        ])]
+    );
+    assert_eq!(
+        uninstall(PackageType::Pacman, pkg.clone()),
+        [argv(&["sudo", "pacman", "-R", "git", "--noconfirm"])]
+    );
+    assert_eq!(
+        update(PackageType::Pacman, pkg),
+        [argv(&["sudo", "pacman", "-Syu", "git", "--noconfirm"])]
+    );
+}
+
+#[test]
+fn dnf() {
+    let pkg = json!({
+        "name": "git",
+        "package_type": "dnf",
+        "version": "2.43.0",
+        "channel": "updates-testing",
+    });
+
+    assert_eq!(
+        install(PackageType::Dnf, pkg.clone()),
+        [argv(&[
+            "sudo",
+            "dnf",
+            "install",
+            "git-2.43.0",
+            "--enablerepo=updates-testing",
+            "-y"
+        ])]
+    );
+    assert_eq!(
+        uninstall(PackageType::Dnf, pkg.clone()),
+        [argv(&["sudo", "dnf", "remove", "git", "-y"])]
+    );
+    assert_eq!(
+        update(PackageType::Dnf, pkg),
+        [argv(&[
+            "sudo",
+            "dnf",
+            "upgrade",
+            "git",
+            "--enablerepo=updates-testing",
+            "-y"
+        ])]
+    );
+}
+
+#[test]
+fn flatpak() {
+    let pkg = json!({
+        "name": "org.gimp.GIMP",
+        "package_type": "flatpak",
+        "channel": "beta",
+        "remote": "flathub-beta",
+        "scope": "user",
+    });
+
+    assert_eq!(
+        install(PackageType::Flatpak, pkg.clone()),
+        [argv(&[
+            "flatpak",
+            "install",
+            "--user",
+            "-y",
+            "flathub-beta",
+            "org.gimp.GIMP//beta"
+        ])]
+    );
+    assert_eq!(
+        uninstall(PackageType::Flatpak, pkg.clone()),
+        [argv(&[
+            "flatpak",
+            "uninstall",
+            "--user",
+            "-y",
+            "org.gimp.GIMP//beta"
+        ])]
+    );
+    assert_eq!(
+        update(PackageType::Flatpak, pkg.clone()),
+        [argv(&[
+            "flatpak",
+            "update",
+            "--user",
+            "-y",
+            "org.gimp.GIMP//beta"
+        ])]
+    );
+    assert_eq!(
+        reinstall(PackageType::Flatpak, pkg),
+        [argv(&[
+            "flatpak",
+            "install",
+            "--reinstall",
+            "--user",
+            "-y",
+            "flathub-beta",
+            "org.gimp.GIMP//beta"
+        ])]
+    );
+}
+
+#[test]
+fn cargo() {
+    let pkg = json!({
+        "name": "ripgrep",
+        "package_type": "cargo",
+        "version": "14.1.0",
+        "features": ["pcre2"],
+        "locked": true,
+    });
+
+    assert_eq!(
+        install(PackageType::Cargo, pkg.clone()),
+        [argv(&[
+            "cargo",
+            "install",
+            "ripgrep",
+            "--version",
+            "14.1.0",
+            "--features",
+            "pcre2",
+            "--locked"
+        ])]
+    );
+    assert_eq!(
+        uninstall(PackageType::Cargo, pkg.clone()),
+        [argv(&["cargo", "uninstall", "ripgrep"])]
+    );
+    // Backends that already replace installed packages reinstall by installing
+    assert_eq!(
+        reinstall(PackageType::Cargo, pkg.clone()),
+        install(PackageType::Cargo, pkg)
+    );
+    assert_eq!(
+        install(
+            PackageType::Cargo,
+            json!({ "name": "tool", "package_type": "cargo", "git": "https://example.com/tool" })
+        ),
+        [argv(&[
+            "cargo",
+            "install",
+            "--git",
+            "https://example.com/tool",
+            "tool"
+        ])]
+    );
+}
+
+#[test]
+fn pipx() {
+    let pkg = json!({
+        "name": "black",
+        "package_type": "pipx",
+        "version": "24.1.0",
+        "extras": ["d", "jupyter"],
+    });
+
+    assert_eq!(
+        install(PackageType::Pipx, pkg.clone()),
+        [argv(&["pipx", "install", "black[d,jupyter]==24.1.0"])]
+    );
+    assert_eq!(
+        uninstall(PackageType::Pipx, pkg.clone()),
+        [argv(&["pipx", "uninstall", "black"])]
+    );
+    assert_eq!(
+        update(PackageType::Pipx, pkg.clone()),
+        [argv(&["pipx", "upgrade", "black"])]
+    );
+    assert_eq!(
+        reinstall(PackageType::Pipx, pkg),
+        [argv(&[
+            "pipx",
+            "install",
+            "--force",
+            "black[d,jupyter]==24.1.0"
+        ])]
+    );
+}
+
+#[test]
+fn npm() {
+    let pkg = json!({
+        "name": "typescript",
+        "package_type": "npm",
+        "version": "5.3.3",
+        "registry": "https://registry.example.com",
+    });
+
+    assert_eq!(
+        install(PackageType::Npm, pkg.clone()),
+        [argv(&[
+            "npm",
+            "install",
+            "-g",
+            "typescript@5.3.3",
+            "--registry=https://registry.example.com"
+        ])]
+    );
+    assert_eq!(
+        uninstall(PackageType::Npm, pkg.clone()),
+        [argv(&["npm", "uninstall", "-g", "typescript"])]
+    );
+    assert_eq!(
+        update(PackageType::Npm, pkg),
+        [argv(&[
+            "npm",
+            "update",
+            "-g",
+            "typescript",
+            "--registry=https://registry.example.com"
+        ])]
+    );
+}
+
+fn install_batch(package_type: PackageType, pkgs: &[serde_json::Value]) -> Vec<Vec<String>> {
+    let env = Env::new();
+    let runner = RecordingRunner::new();
+    let registry = env.registry(&runner);
+    let backend = registry.get(&package_type).unwrap();
+
+    let pkgs: Vec<_> = pkgs.iter().map(|p| data(p.clone())).collect();
+    assert!(pkgs.iter().all(|p| backend.can_batch(p)));
+
+    let ok = backend
+        .install_batch(registry.runner(), &pkgs.iter().collect::<Vec<_>>())
+        .unwrap();
+
+    assert!(ok);
+    runner.calls()
+}
+
+#[test]
+fn batch_installs() {
+    assert_eq!(
+        install_batch(
+            PackageType::Apt,
+            &[
+                json!({ "name": "curl", "package_type": "apt", "version": "7.88" }),
+                json!({ "name": "git", "package_type": "apt" }),
+            ]
+        ),
+        [argv(&["sudo", "apt", "install", "curl=7.88", "git", "-y"])]
+    );
+    assert_eq!(
+        install_batch(
+            PackageType::Dnf,
+            &[
+                json!({ "name": "curl", "package_type": "dnf", "version": "8.0" }),
+                json!({ "name": "git", "package_type": "dnf" }),
+            ]
+        ),
+        [argv(&["sudo", "dnf", "install", "curl-8.0", "git", "-y"])]
+    );
+    assert_eq!(
+        install_batch(
+            PackageType::Pacman,
+            &[
+                json!({ "name": "curl", "package_type": "pacman" }),
+                json!({ "name": "git", "package_type": "pacman" }),
+            ]
+        ),
+        [argv(&[
+            "sudo",
+            "pacman",
+            "-S",
+            "--needed",
+            "curl",
+            "git",
+            "--noconfirm"
+        ])]
+    );
+    assert_eq!(
+        install_batch(
+            PackageType::Brew,
+            &[
+                json!({ "name": "python", "package_type": "brew", "version": "3.12" }),
+                json!({ "name": "git", "package_type": "brew" }),
+            ]
+        ),
+        [argv(&["brew", "install", "python@3.12", "git"])]
+    );
+}
+
+/// Asks the backend for the installed version while `program` prints `output`
+fn query(
+    package_type: PackageType,
+    pkg: serde_json::Value,
+    program: &str,
+    output: &str,
+) -> (Option<String>, Vec<Vec<String>>) {
+    let env = Env::new();
+    let runner = RecordingRunner::new();
+    let registry = env.registry(&runner);
+
+    runner.set_output(program, output);
+
+    let version = registry
+        .get(&package_type)
+        .unwrap()
+        .query(registry.runner(), &data(pkg))
+        .unwrap();
+
+    (version, runner.queries())
+}
+
+#[test]
+fn queries() {
+    assert_eq!(
+        query(
+            PackageType::Apt,
+            json!({ "name": "curl", "package_type": "apt" }),
+            "dpkg-query",
+            "7.88.1-10"
+        ),
+        (
+            Some("7.88.1-10".to_string()),
+            vec![argv(&["dpkg-query", "-W", "-f=${Version}", "curl"])]
+        )
+    );
+    assert_eq!(
+        query(
+            PackageType::Snap,
+            json!({ "name": "code", "package_type": "snap" }),
+            "snap",
+            "Name  Version   Rev  Tracking\ncode  1.85.1    150  latest/stable"
+        ),
+        (
+            Some("1.85.1".to_string()),
+            vec![argv(&["snap", "list", "code"])]
+        )
+    );
+    assert_eq!(
+        query(
+            PackageType::Brew,
+            json!({ "name": "python", "package_type": "brew" }),
+            "brew",
+            "python 3.12.0 3.12.1_1"
+        ),
+        (
+            Some("3.12.1_1".to_string()),
+            vec![argv(&["brew", "list", "--versions", "python"])]
+        )
+    );
+    assert_eq!(
+        query(
+            PackageType::Pacman,
+            json!({ "name": "git", "package_type": "pacman" }),
+            "pacman",
+            "git 2.43.0-1"
+        ),
+        (
+            Some("2.43.0-1".to_string()),
+            vec![argv(&["pacman", "-Q", "git"])]
+        )
+    );
+    assert_eq!(
+        query(
+            PackageType::Dnf,
+            json!({ "name": "git", "package_type": "dnf" }),
+            "rpm",
+            "2.43.0-1.fc39"
+        ),
+        (
+            Some("2.43.0-1.fc39".to_string()),
+            vec![argv(&[
+                "rpm",
+                "-q",
+                "--queryformat",
+                "%{VERSION}-%{RELEASE}",
+                "git"
+            ])]
+        )
+    );
+    assert_eq!(
+        query(
+            PackageType::Flatpak,
+            json!({ "name": "org.gimp.GIMP", "package_type": "flatpak" }),
+            "flatpak",
+            "GIMP\n\n          ID: org.gimp.GIMP\n     Version: 2.10.36"
+        )
+        .0,
+        Some("2.10.36".to_string())
+    );
+    assert_eq!(
+        query(
+            PackageType::Cargo,
+            json!({ "name": "ripgrep", "package_type": "cargo" }),
+            "cargo",
+            "fd-find v9.0.0:\n    fd\nripgrep v14.1.0:\n    rg"
+        ),
+        (
+            Some("14.1.0".to_string()),
+            vec![argv(&["cargo", "install", "--list"])]
+        )
+    );
+    assert_eq!(
+        query(
+            PackageType::Pipx,
+            json!({ "name": "black", "package_type": "pipx" }),
+            "pipx",
+            r#"{"venvs": {"black": {"metadata": {"main_package": {"package_version": "24.1.0"}}}}}"#
+        ),
+        (
+            Some("24.1.0".to_string()),
+            vec![argv(&["pipx", "list", "--json"])]
+        )
+    );
+    assert_eq!(
+        query(
+            PackageType::Npm,
+            json!({ "name": "typescript", "package_type": "npm" }),
+            "npm",
+            r#"{"dependencies": {"typescript": {"version": "5.3.3"}}}"#
+        ),
+        (
+            Some("5.3.3".to_string()),
+            vec![argv(&[
+                "npm",
+                "ls",
+                "-g",
+                "--json",
+                "--depth=0",
+                "typescript"
+            ])]
+        )
+    );
+}
+
+#[test]
+fn git() {
+    let env = Env::new();
+    let runner = RecordingRunner::new();
+    let registry = env.registry(&runner);
+    let dir = env.config.config_dir.join("git").join("tool");
+    let dir = dir.display().to_string();
+    let commit = "0123456789abcdef0123456789abcdef01234567";
+
+    runner.set_output("git", commit);
+
+    let mut pkg = Package::from(data(json!({
+        "name": "tool",
+        "package_type": "git",
+        "repo": "https://example.com/tool.git",
+        "git_ref": "v1.0",
+        "build_commands": ["make"],
+        "install_commands": ["make install"],
+    })));
+
+    let ok = registry
+        .get(&PackageType::Git)
+        .unwrap()
+        .install_package(registry.runner(), &mut pkg)
+        .unwrap();
+
+    assert!(ok);
+    assert_eq!(pkg.package_data.commit.as_deref(), Some(commit));
+    assert_eq!(
+        runner.calls(),
+        [
+            argv(&[
+                "git",
+                "clone",
+                "https://example.com/tool.git",
+                &dir,
+                "--quiet"
+            ]),
+            argv(&["git", "-C", &dir, "checkout", "--quiet", "--detach", commit]),
+            argv(&["sh", "-c", "make"]),
+            argv(&["sh", "-c", "make install"]),
+        ]
+    );
+    assert_eq!(
+        runner.queries(),
+        [argv(&[
+            "git",
+            "-C",
+            &dir,
+            "rev-parse",
+            "--verify",
+            "--quiet",
+            "origin/v1.0^{commit}"
+        ])]
+    );
+}
+
+#[test]
+fn git_without_an_uninstall_step_refuses_to_uninstall() {
+    let env = Env::new();
+    let runner = RecordingRunner::new();
+    let registry = env.registry(&runner);
+
+    let ok = registry
+        .get(&PackageType::Git)
+        .unwrap()
+        .uninstall(
+            registry.runner(),
+            &data(json!({
+                "name": "tool",
+                "package_type": "git",
+                "repo": "https://example.com/tool.git",
+                "install_commands": ["make install"],
+            })),
+        )
+        .unwrap();
+
+    assert!(!ok);
+    assert!(runner.calls().is_empty());
+}
+
+fn run_git(dir: &Path, args: &[&str]) -> String {
+    let output = Command::new("git")
+        .args([
+            "-c",
+            "user.name=nexus",
+            "-c",
+            "user.email=nexus@example.com",
+        ])
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "git {:?} failed", args);
+    String::from_utf8_lossy(&output.stdout).trim().to_string()
+}
+
+/// Creates a repo with a single commit on `main`
+fn init_repo(repo: &Path, message: &str) {
+    create_dir_all(repo).unwrap();
+    run_git(repo, &["init", "--quiet"]);
+    run_git(repo, &["checkout", "--quiet", "-b", "main"]);
+    run_git(repo, &["commit", "--quiet", "--allow-empty", "-m", message]);
+}
+
+#[test]
+fn git_builds_from_a_local_repo() {
+    let env = Env::new();
+    let registry = Registry::new(&env.config, Box::new(SystemRunner));
+    let backend = registry.get(&PackageType::Git).unwrap();
+    let runner = registry.runner();
+
+    let repo = env.dir.path().join("repo");
+    init_repo(&repo, "first");
+
+    // Steps run inside the checkout, so this logs next to it
+    let log = env.config.config_dir.join("git").join("log");
+    let checkout = env.config.config_dir.join("git").join("tool");
+    let steps = || read_to_string(&log).unwrap_or_default();
+
+    let mut pkg = Package::from(data(json!({
+        "name": "tool",
+        "package_type": "git",
+        "repo": repo.display().to_string(),
+        "git_ref": "main",
+        "build_commands": ["echo build >> ../log"],
+        "install_commands": ["echo install >> ../log"],
+        "uninstall_commands": ["echo uninstall >> ../log"],
+    })));
+
+    assert!(backend.install_package(runner, &mut pkg).unwrap());
+    assert_eq!(steps(), "build\ninstall\n");
+    assert_eq!(
+        pkg.package_data.commit.as_deref(),
+        Some(run_git(&repo, &["rev-parse", "HEAD"]).as_str())
+    );
+    assert_eq!(
+        run_git(&checkout, &["rev-parse", "HEAD"]),
+        run_git(&repo, &["rev-parse", "HEAD"])
+    );
+
+    // The ref hasn't moved, so nothing is rebuilt
+    assert!(backend.update_package(runner, &mut pkg).unwrap());
+    assert_eq!(steps(), "build\ninstall\n");
+
+    run_git(
+        &repo,
+        &["commit", "--quiet", "--allow-empty", "-m", "second"],
+    );
+
+    assert!(backend.update_package(runner, &mut pkg).unwrap());
+    assert_eq!(steps(), "build\ninstall\nbuild\ninstall\n");
+    assert_eq!(
+        pkg.package_data.commit.as_deref(),
+        Some(run_git(&repo, &["rev-parse", "HEAD"]).as_str())
+    );
+
+    assert!(backend.uninstall(runner, &pkg.package_data).unwrap());
+    assert_eq!(steps(), "build\ninstall\nbuild\ninstall\nuninstall\n");
+    assert!(!checkout.exists());
+}
+
+#[test]
+fn url_downloads_with_curl() {
+    let env = Env::new();
+    let runner = RecordingRunner::new();
+    let registry = env.registry(&runner);
+    let download = env
+        .config
+        .data_dir
+        .join("url/staging/tool/download")
+        .display()
+        .to_string();
+
+    // Nothing is downloaded, so there is nothing to verify either
+    let result = registry.get(&PackageType::Url).unwrap().install(
+        registry.runner(),
+        &data(json!({
+            "name": "tool",
+            "package_type": "url",
+            "version": "1.2.0",
+            "url": "https://example.com/{name}-{version}",
+            "sha256": "00",
+        })),
+    );
+
+    assert!(result.is_err());
+    assert_eq!(
+        runner.calls(),
+        [argv(&[
+            "curl",
+            "-fsSL",
+            "-o",
+            &download,
+            "https://example.com/tool-1.2.0"
+        ])]
+    );
+}
+
+/// Writes `files` into `dir` and packs them into `archive` with tar
+fn tar_gz(archive: &Path, dir: &Path, files: &[(&str, &str)]) {
+    create_dir_all(dir).unwrap();
+    create_dir_all(archive.parent().unwrap()).unwrap();
+
+    for (name, contents) in files {
+        write(dir.join(name), contents).unwrap();
+    }
+
+    let names: Vec<&str> = files.iter().map(|(name, _)| *name).collect();
+    let status = Command::new("tar")
+        .arg("-czf")
+        .arg(archive)
+        .arg("-C")
+        .arg(dir)
+        .args(names)
+        .status()
+        .unwrap();
+
+    assert!(status.success());
+}
+
+fn url_package(url: &Path, file: &Path, extra: serde_json::Value) -> PackageData {
+    let mut pkg = json!({
+        "name": "tool",
+        "package_type": "url",
+        "url": format!("file://{}", url.display()),
+        "sha256": package::hash_file(file).unwrap(),
+    });
+
+    for (key, value) in extra.as_object().unwrap() {
+        pkg[key] = value.clone();
+    }
+
+    data(pkg)
+}
+
+/// The files in `dir`, sorted
+fn file_names(dir: &Path) -> Vec<String> {
+    let mut names: Vec<String> = read_dir(dir)
+        .unwrap()
+        .map(|e| e.unwrap().file_name().to_string_lossy().to_string())
+        .collect();
+    names.sort();
+    names
+}
+
+#[test]
+fn url_rejects_downloads_with_the_wrong_sha256() {
+    let env = Env::new();
+    let registry = Registry::new(&env.config, Box::new(SystemRunner));
+    let backend = registry.get(&PackageType::Url).unwrap();
+    let bin_dir = env.config.data_dir.join("bin");
+
+    let download = env.dir.path().join("tool");
+    write(&download, "#!/bin/sh\necho tool\n").unwrap();
+
+    let mut pkg = url_package(&download, &download, json!({}));
+    pkg.sha256 = Some("0".repeat(64));
+
+    let Err(e) = backend.install(registry.runner(), &pkg) else {
+        panic!("expected a checksum mismatch");
+    };
+
+    assert!(e.to_string().starts_with("Checksum mismatch"));
+    assert!(!bin_dir.join("tool").exists());
+    assert!(!env.config.data_dir.join("url/staging/tool").exists());
+}
+
+#[test]
+fn url_places_and_removes_a_raw_download() {
+    let env = Env::new();
+    let registry = Registry::new(&env.config, Box::new(SystemRunner));
+    let backend = registry.get(&PackageType::Url).unwrap();
+    let bin_dir = env.config.data_dir.join("bin");
+
+    let download = env.dir.path().join("tool-linux");
+    write(&download, "#!/bin/sh\necho tool\n").unwrap();
+
+    // Somebody else's file in the same dir
+    create_dir_all(&bin_dir).unwrap();
+    write(bin_dir.join("other"), "").unwrap();
+
+    let pkg = url_package(&download, &download, json!({ "files": ["tool"] }));
+
+    assert!(backend.install(registry.runner(), &pkg).unwrap());
+    assert_eq!(file_names(&bin_dir), ["other", "tool"]);
+    assert_eq!(
+        read_to_string(bin_dir.join("tool")).unwrap(),
+        "#!/bin/sh\necho tool\n"
+    );
+
+    assert!(backend.uninstall(registry.runner(), &pkg).unwrap());
+    assert_eq!(file_names(&bin_dir), ["other"]);
+
+    // Nothing is recorded anymore, so nothing is known to remove
+    assert!(!backend.uninstall(registry.runner(), &pkg).unwrap());
+}
+
+#[test]
+fn url_reinstall_removes_files_the_new_version_no_longer_ships() {
+    let env = Env::new();
+    let registry = Registry::new(&env.config, Box::new(SystemRunner));
+    let backend = registry.get(&PackageType::Url).unwrap();
+    let bin_dir = env.config.data_dir.join("bin");
+    let releases = env.dir.path().join("releases");
+    let template = releases.join("tool-{version}.tar.gz");
+
+    let v1 = releases.join("tool-1.0.tar.gz");
+    tar_gz(
+        &v1,
+        &env.dir.path().join("v1"),
+        &[("tool", "1.0"), ("tool-helper", "1.0")],
+    );
+
+    let v2 = releases.join("tool-2.0.tar.gz");
+    tar_gz(&v2, &env.dir.path().join("v2"), &[("tool", "2.0")]);
+
+    create_dir_all(&bin_dir).unwrap();
+    write(bin_dir.join("other"), "").unwrap();
+
+    let pkg = url_package(
+        &template,
+        &v1,
+        json!({ "version": "1.0", "format": "tar.gz", "files": ["tool", "tool-helper"] }),
+    );
+    assert!(backend.install(registry.runner(), &pkg).unwrap());
+    assert_eq!(file_names(&bin_dir), ["other", "tool", "tool-helper"]);
+
+    let pkg = url_package(
+        &template,
+        &v2,
+        json!({ "version": "2.0", "format": "tar.gz", "files": ["tool"] }),
+    );
+    assert!(backend.install(registry.runner(), &pkg).unwrap());
+    assert_eq!(file_names(&bin_dir), ["other", "tool"]);
+    assert_eq!(read_to_string(bin_dir.join("tool")).unwrap(), "2.0");
+
+    assert!(backend.uninstall(registry.runner(), &pkg).unwrap());
+    assert_eq!(file_names(&bin_dir), ["other"]);
+}
+
+#[test]
+fn url_refuses_to_overwrite_another_packages_file() {
+    let env = Env::new();
+    let registry = Registry::new(&env.config, Box::new(SystemRunner));
+    let backend = registry.get(&PackageType::Url).unwrap();
+    let bin_dir = env.config.data_dir.join("bin");
+
+    let first = env.dir.path().join("first");
+    write(&first, "first").unwrap();
+    let second = env.dir.path().join("second");
+    write(&second, "second").unwrap();
+
+    let pkg = url_package(&first, &first, json!({ "files": ["tool"] }));
+    assert!(backend.install(registry.runner(), &pkg).unwrap());
+
+    let other = url_package(
+        &second,
+        &second,
+        json!({ "name": "other-tool", "files": ["tool"] }),
+    );
+    let Err(e) = backend.install(registry.runner(), &other) else {
+        panic!("expected the install to be refused");
+    };
+
+    assert!(e.to_string().starts_with("Refusing to overwrite"));
+    assert_eq!(read_to_string(bin_dir.join("tool")).unwrap(), "first");
+
+    // Nothing was placed, so there is nothing to remove for the refused package
+    assert!(!backend.uninstall(registry.runner(), &other).unwrap());
+    assert_eq!(file_names(&bin_dir), ["tool"]);
+}
+
+#[test]
+fn git_follows_a_changed_repo() {
+    let env = Env::new();
+    let registry = Registry::new(&env.config, Box::new(SystemRunner));
+    let backend = registry.get(&PackageType::Git).unwrap();
+    let runner = registry.runner();
+
+    let upstream = env.dir.path().join("upstream");
+    let fork = env.dir.path().join("fork");
+    init_repo(&upstream, "upstream");
+    init_repo(&fork, "fork");
+
+    let mut pkg = Package::from(data(json!({
+        "name": "tool",
+        "package_type": "git",
+        "repo": upstream.display().to_string(),
+        "git_ref": "main",
+    })));
+    assert!(backend.install_package(runner, &mut pkg).unwrap());
+
+    pkg.package_data.repo = Some(fork.display().to_string());
+    assert!(backend.reinstall_package(runner, &mut pkg).unwrap());
+
+    let fork_head = run_git(&fork, &["rev-parse", "HEAD"]);
+    let checkout = env.config.config_dir.join("git").join("tool");
+
+    assert_eq!(pkg.package_data.commit.as_deref(), Some(fork_head.as_str()));
+    assert_eq!(run_git(&checkout, &["rev-parse", "HEAD"]), fork_head);
+}
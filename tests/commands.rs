@@ -0,0 +1,672 @@
+mod common;
+
+use common::{argv, data, Env, Fake};
+
+use mlua::Lua;
+use nexus::commands;
+use nexus::package::{self, PackageType};
+use nexus::runner::RecordingRunner;
+use nexus::state;
+use serde_json::json;
+
+const RIPGREP: &str = r#"return { name = "ripgrep", package_type = "cargo" }"#;
+
+#[test]
+fn install_records_installed_packages() {
+    let lua = Lua::new();
+    let mut env = Env::new();
+    env.add_package("ripgrep.lua", RIPGREP);
+
+    let runner = RecordingRunner::new();
+    commands::install(&env.registry(&runner), &env.config, &env.packages(&lua)).unwrap();
+
+    assert_eq!(runner.calls(), [argv(&["cargo", "install", "ripgrep"])]);
+
+    let installed = env.installed();
+    assert_eq!(installed.len(), 1);
+    assert_eq!(installed[0].name, "ripgrep");
+}
+
+#[test]
+fn install_skips_packages_with_same_hash() {
+    let lua = Lua::new();
+    let mut env = Env::new();
+    env.add_package("ripgrep.lua", RIPGREP);
+
+    let runner = RecordingRunner::new();
+    commands::install(&env.registry(&runner), &env.config, &env.packages(&lua)).unwrap();
+
+    let runner = RecordingRunner::new();
+    commands::install(&env.registry(&runner), &env.config, &env.packages(&lua)).unwrap();
+
+    assert!(runner.calls().is_empty());
+    assert_eq!(env.installed().len(), 1);
+}
+
+#[test]
+fn install_reinstalls_when_source_file_changes() {
+    let lua = Lua::new();
+    let mut env = Env::new();
+    let deb = env.config.config_dir.join("packages").join("tool.deb");
+    std::fs::write(&deb, "v1").unwrap();
+
+    // Source hashes are tracked for every package type, cargo is used since it
+    // runs on any host
+    env.add_package(
+        "tool.lua",
+        r#"return { name = "tool", package_type = "cargo", source = "tool.deb" }"#,
+    );
+
+    let runner = RecordingRunner::new();
+    commands::install(&env.registry(&runner), &env.config, &env.packages(&lua)).unwrap();
+
+    std::fs::write(&deb, "v2").unwrap();
+
+    let runner = RecordingRunner::new();
+    commands::install(&env.registry(&runner), &env.config, &env.packages(&lua)).unwrap();
+
+    assert_eq!(runner.calls(), [argv(&["cargo", "install", "tool"])]);
+
+    let installed = env.installed();
+    assert_eq!(installed.len(), 1);
+    assert_eq!(
+        installed[0].source_hash,
+        Some(package::hash_file(&deb).unwrap())
+    );
+}
+
+#[test]
+fn sync_backs_up_the_state_from_before_the_run() {
+    let lua = Lua::new();
+    let mut env = Env::new();
+    env.add_package("ripgrep.lua", RIPGREP);
+
+    state::save(
+        &env.config,
+        &[
+            data(json!({ "name": "fd-find", "package_type": "cargo" })),
+            data(json!({ "name": "bat", "package_type": "cargo" })),
+        ],
+    )
+    .unwrap();
+    let before = std::fs::read_to_string(state::path(&env.config)).unwrap();
+
+    // Two removals and an install, each saved as it happens
+    let runner = RecordingRunner::new();
+    commands::sync(&env.registry(&runner), &env.config, &env.packages(&lua)).unwrap();
+
+    assert_eq!(runner.calls().len(), 3);
+    assert_eq!(
+        std::fs::read_to_string(state::backup_path(&env.config)).unwrap(),
+        before
+    );
+}
+
+/// Installs a package built from a local file, changes the file without
+/// changing its version and returns what the second install ran
+fn reinstall_changed_file(
+    package_type: PackageType,
+    file_name: &str,
+) -> (String, Vec<Vec<String>>) {
+    let lua = Lua::new();
+    let mut env = Env::new();
+    let file = env.config.config_dir.join("packages").join(file_name);
+    std::fs::write(&file, "v1").unwrap();
+
+    env.add_package(
+        "tool.lua",
+        &format!(
+            r#"return {{ name = "tool", package_type = "{}", source = "{}" }}"#,
+            package_type, file_name
+        ),
+    );
+    let package_types = [package_type];
+
+    let runner = RecordingRunner::new();
+    let registry = env.registry_on_any_os(&runner, &package_types);
+    commands::install(&registry, &env.config, &env.packages(&lua)).unwrap();
+
+    std::fs::write(&file, "v1, rebuilt").unwrap();
+
+    let runner = RecordingRunner::new();
+    let registry = env.registry_on_any_os(&runner, &package_types);
+    commands::install(&registry, &env.config, &env.packages(&lua)).unwrap();
+
+    let source = env.installed().remove(0).source.unwrap();
+    (source, runner.calls())
+}
+
+#[test]
+fn install_reinstalls_changed_debs() {
+    let (deb, calls) = reinstall_changed_file(PackageType::Apt, "tool.deb");
+
+    assert_eq!(
+        calls,
+        [argv(&[
+            "sudo",
+            "apt",
+            "install",
+            "--reinstall",
+            "--allow-downgrades",
+            &deb,
+            "-y"
+        ])]
+    );
+}
+
+#[test]
+fn install_reinstalls_changed_rpms() {
+    let (rpm, calls) = reinstall_changed_file(PackageType::Dnf, "tool.rpm");
+
+    assert_eq!(calls, [argv(&["sudo", "dnf", "reinstall", &rpm, "-y"])]);
+}
+
+#[test]
+fn install_does_not_record_failed_packages() {
+    let lua = Lua::new();
+    let mut env = Env::new();
+    env.add_package("ripgrep.lua", RIPGREP);
+
+    let runner = RecordingRunner::failing();
+    commands::install(&env.registry(&runner), &env.config, &env.packages(&lua)).unwrap();
+
+    assert_eq!(runner.calls().len(), 1);
+    assert!(env.installed().is_empty());
+}
+
+#[test]
+fn postinstall_only_runs_after_a_successful_install() {
+    let lua = Lua::new();
+    let mut env = Env::new();
+    env.add_package(
+        "ripgrep.lua",
+        r#"return {
+            name = "ripgrep",
+            package_type = "cargo",
+            post_install = function() ripgrep_hook = true end,
+        }"#,
+    );
+
+    // Batched packages go through their own path
+    for name in ["a", "b"] {
+        env.add_package(
+            &format!("{}.lua", name),
+            &format!(
+                r#"return {{
+                    name = "{0}",
+                    package_type = "fake",
+                    post_install = function() {0}_hook = true end,
+                }}"#,
+                name
+            ),
+        );
+    }
+
+    let runner = RecordingRunner::failing();
+    let mut registry = env.registry(&runner);
+    Fake::new("fake").batching().register(&mut registry);
+
+    commands::install(&registry, &env.config, &env.packages(&lua)).unwrap();
+
+    let hook = |name: &str| lua.globals().get::<Option<bool>>(name).unwrap();
+    assert_eq!(hook("ripgrep_hook"), None);
+    assert_eq!(hook("a_hook"), None);
+    assert_eq!(hook("b_hook"), None);
+}
+
+#[test]
+fn purge_uninstalls_undeclared_packages() {
+    let lua = Lua::new();
+    let mut env = Env::new();
+    env.add_package("ripgrep.lua", RIPGREP);
+
+    let runner = RecordingRunner::new();
+    commands::install(&env.registry(&runner), &env.config, &env.packages(&lua)).unwrap();
+
+    let mut installed = env.installed();
+    installed.push(data(json!({ "name": "fd-find", "package_type": "cargo" })));
+    state::save(&env.config, &installed).unwrap();
+
+    let runner = RecordingRunner::new();
+    commands::purge(&env.registry(&runner), &env.config, &env.packages(&lua)).unwrap();
+
+    assert_eq!(runner.calls(), [argv(&["cargo", "uninstall", "fd-find"])]);
+
+    let installed = env.installed();
+    assert_eq!(installed.len(), 1);
+    assert_eq!(installed[0].name, "ripgrep");
+}
+
+#[test]
+fn purge_keeps_packages_their_backend_refused_to_remove() {
+    let env = Env::new();
+
+    // Neither says how to undo its install
+    state::save(
+        &env.config,
+        &[
+            data(json!({
+                "name": "tool",
+                "package_type": "git",
+                "repo": "https://example.com/tool.git",
+            })),
+            data(json!({
+                "name": "fetched",
+                "package_type": "url",
+                "url": "https://example.com/fetched",
+                "sha256": "00",
+            })),
+        ],
+    )
+    .unwrap();
+
+    let runner = RecordingRunner::new();
+    commands::purge(&env.registry(&runner), &env.config, &[]).unwrap();
+
+    assert!(runner.calls().is_empty());
+
+    let names: Vec<String> = env.installed().into_iter().map(|p| p.name).collect();
+    assert_eq!(names, ["tool", "fetched"]);
+}
+
+#[test]
+fn update_upgrades_arch_systems_only_once() {
+    let lua = Lua::new();
+    let mut env = Env::new();
+    env.add_package(
+        "git.lua",
+        r#"return { name = "git", package_type = "pacman" }"#,
+    );
+    env.add_package(
+        "vim.lua",
+        r#"return { name = "vim", package_type = "pacman" }"#,
+    );
+
+    let pkgs = env.packages(&lua);
+    let installed: Vec<_> = pkgs.iter().map(|p| p.package_data.clone()).collect();
+    state::save(&env.config, &installed).unwrap();
+
+    let runner = RecordingRunner::new();
+    let registry = env.registry_on_any_os(&runner, &[PackageType::Pacman]);
+    commands::update(&registry, &env.config, &pkgs).unwrap();
+
+    assert_eq!(
+        runner.calls(),
+        [
+            argv(&["sudo", "pacman", "-Syu", "git", "--noconfirm"]),
+            argv(&["sudo", "pacman", "-S", "--needed", "vim", "--noconfirm"]),
+        ]
+    );
+}
+
+#[test]
+fn update_skips_pinned_packages() {
+    let lua = Lua::new();
+    let env = Env::new();
+
+    state::save(
+        &env.config,
+        &[
+            data(json!({ "name": "ripgrep", "package_type": "cargo" })),
+            data(json!({ "name": "fd-find", "package_type": "cargo", "version": "9.0.0" })),
+        ],
+    )
+    .unwrap();
+
+    let runner = RecordingRunner::new();
+    commands::update(&env.registry(&runner), &env.config, &env.packages(&lua)).unwrap();
+
+    assert_eq!(runner.calls(), [argv(&["cargo", "install", "ripgrep"])]);
+}
+
+#[test]
+fn sync_removes_adds_modifies_and_upgrades_in_order() {
+    let lua = Lua::new();
+    let mut env = Env::new();
+    env.add_package("ripgrep.lua", RIPGREP);
+    env.add_package(
+        "bat.lua",
+        r#"return { name = "bat", package_type = "cargo" }"#,
+    );
+
+    let runner = RecordingRunner::new();
+    commands::install(&env.registry(&runner), &env.config, &env.packages(&lua)).unwrap();
+
+    let mut installed = env.installed();
+    installed.push(data(json!({ "name": "fd-find", "package_type": "cargo" })));
+    state::save(&env.config, &installed).unwrap();
+
+    env.add_package(
+        "bat.lua",
+        r#"return { name = "bat", package_type = "cargo", locked = true }"#,
+    );
+    env.add_package(
+        "tokei.lua",
+        r#"return { name = "tokei", package_type = "cargo" }"#,
+    );
+
+    let runner = RecordingRunner::new();
+    commands::sync(&env.registry(&runner), &env.config, &env.packages(&lua)).unwrap();
+
+    assert_eq!(
+        runner.calls(),
+        [
+            argv(&["cargo", "uninstall", "fd-find"]),
+            argv(&["cargo", "install", "tokei"]),
+            argv(&["cargo", "install", "bat", "--locked"]),
+            argv(&["cargo", "install", "ripgrep"]),
+        ]
+    );
+
+    let mut names: Vec<String> = env.installed().into_iter().map(|p| p.name).collect();
+    names.sort();
+    assert_eq!(names, ["bat", "ripgrep", "tokei"]);
+}
+
+fn fake_package(name: &str) -> String {
+    format!(r#"return {{ name = "{}", package_type = "fake" }}"#, name)
+}
+
+#[test]
+fn install_batches_packages_of_the_same_type() {
+    let lua = Lua::new();
+    let mut env = Env::new();
+    env.add_package("a.lua", &fake_package("a"));
+    env.add_package("b.lua", &fake_package("b"));
+    env.add_package("ripgrep.lua", RIPGREP);
+
+    let runner = RecordingRunner::new();
+    let mut registry = env.registry(&runner);
+    Fake::new("fake").batching().register(&mut registry);
+
+    commands::install(&registry, &env.config, &env.packages(&lua)).unwrap();
+
+    assert_eq!(
+        runner.calls(),
+        [
+            argv(&["fake", "install", "a", "b"]),
+            argv(&["cargo", "install", "ripgrep"]),
+        ]
+    );
+    assert_eq!(env.installed().len(), 3);
+}
+
+#[test]
+fn install_retries_failed_batches_one_at_a_time() {
+    let lua = Lua::new();
+    let mut env = Env::new();
+    env.add_package("a.lua", &fake_package("a"));
+    env.add_package("broken.lua", &fake_package("broken"));
+
+    let runner = RecordingRunner::new();
+    let mut registry = env.registry(&runner);
+    Fake::new("fake").batching().register(&mut registry);
+
+    commands::install(&registry, &env.config, &env.packages(&lua)).unwrap();
+
+    assert_eq!(
+        runner.calls(),
+        [
+            argv(&["fake", "install", "a", "broken"]),
+            argv(&["fake", "install", "a"]),
+            argv(&["fake", "install", "broken"]),
+        ]
+    );
+
+    let installed = env.installed();
+    assert_eq!(installed.len(), 1);
+    assert_eq!(installed[0].name, "a");
+}
+
+#[test]
+fn install_runs_backends_in_parallel_jobs() {
+    let lua = Lua::new();
+    let mut env = Env::new();
+    env.add_package("a.lua", &fake_package("a"));
+    env.add_package("b.lua", &fake_package("b"));
+    env.add_package("ripgrep.lua", RIPGREP);
+    env.add_package(
+        "bat.lua",
+        r#"return { name = "bat", package_type = "cargo" }"#,
+    );
+
+    let runner = RecordingRunner::new();
+    let mut registry = env.registry(&runner);
+    Fake::new("fake").batching().register(&mut registry);
+    registry.set_jobs(2);
+
+    commands::install(&registry, &env.config, &env.packages(&lua)).unwrap();
+
+    // Jobs interleave, but each backend keeps its own order
+    let calls = runner.calls();
+    let cargo: Vec<_> = calls.iter().filter(|c| c[0] == "cargo").collect();
+    let fake: Vec<_> = calls.iter().filter(|c| c[0] == "fake").collect();
+
+    assert_eq!(calls.len(), 3);
+    assert_eq!(
+        cargo,
+        [
+            &argv(&["cargo", "install", "ripgrep"]),
+            &argv(&["cargo", "install", "bat"]),
+        ]
+    );
+    assert_eq!(fake, [&argv(&["fake", "install", "a", "b"])]);
+
+    let mut names: Vec<String> = env.installed().into_iter().map(|p| p.name).collect();
+    names.sort();
+    assert_eq!(names, ["a", "b", "bat", "ripgrep"]);
+}
+
+#[test]
+fn install_skips_packages_whose_dependencies_failed() {
+    let lua = Lua::new();
+    let mut env = Env::new();
+    env.add_package("broken.lua", &fake_package("broken"));
+    env.add_package(
+        "ripgrep.lua",
+        r#"return { name = "ripgrep", package_type = "cargo", depends_on = { "broken" } }"#,
+    );
+
+    let runner = RecordingRunner::new();
+    let mut registry = env.registry(&runner);
+    Fake::new("fake").batching().register(&mut registry);
+
+    commands::install(&registry, &env.config, &env.packages(&lua)).unwrap();
+
+    assert_eq!(runner.calls(), [argv(&["fake", "install", "broken"])]);
+    assert!(env.installed().is_empty());
+}
+
+#[test]
+fn purge_keeps_packages_declared_packages_depend_on() {
+    let lua = Lua::new();
+    let mut env = Env::new();
+    env.add_package(
+        "ripgrep.lua",
+        r#"return { name = "ripgrep", package_type = "cargo", depends_on = { "rustup" } }"#,
+    );
+
+    state::save(
+        &env.config,
+        &[data(json!({ "name": "rustup", "package_type": "cargo" }))],
+    )
+    .unwrap();
+
+    let runner = RecordingRunner::new();
+    commands::purge(&env.registry(&runner), &env.config, &env.packages(&lua)).unwrap();
+
+    assert!(runner.calls().is_empty());
+    assert_eq!(env.installed().len(), 1);
+}
+
+#[test]
+fn install_only_reinstalls_the_changed_package_of_a_file() {
+    let lua = Lua::new();
+    let mut env = Env::new();
+    env.add_package(
+        "tools.lua",
+        r#"return {
+            { name = "ripgrep", package_type = "cargo" },
+            { name = "fd-find", package_type = "cargo" },
+        }"#,
+    );
+
+    let runner = RecordingRunner::new();
+    commands::install(&env.registry(&runner), &env.config, &env.packages(&lua)).unwrap();
+
+    env.add_package(
+        "tools.lua",
+        r#"return {
+            { name = "ripgrep", package_type = "cargo" },
+            { name = "fd-find", package_type = "cargo", locked = true },
+        }"#,
+    );
+
+    let runner = RecordingRunner::new();
+    commands::install(&env.registry(&runner), &env.config, &env.packages(&lua)).unwrap();
+
+    assert_eq!(
+        runner.calls(),
+        [argv(&["cargo", "install", "fd-find", "--locked"])]
+    );
+}
+
+#[test]
+fn install_skips_packages_recorded_with_their_file_hash() {
+    let lua = Lua::new();
+    let mut env = Env::new();
+    let path = env.add_package("ripgrep.lua", RIPGREP);
+
+    state::save(
+        &env.config,
+        &[data(json!({
+            "name": "ripgrep",
+            "package_type": "cargo",
+            "hash": package::hash_file(&path).unwrap(),
+        }))],
+    )
+    .unwrap();
+
+    let runner = RecordingRunner::new();
+    commands::install(&env.registry(&runner), &env.config, &env.packages(&lua)).unwrap();
+
+    assert!(runner.calls().is_empty());
+}
+
+#[test]
+fn install_reinstalls_changed_definitions_in_place() {
+    let lua = Lua::new();
+    let mut env = Env::new();
+    env.add_package(
+        "ripgrep.lua",
+        r#"return { name = "ripgrep", package_type = "cargo", version = "13.0.0" }"#,
+    );
+
+    let runner = RecordingRunner::new();
+    commands::install(&env.registry(&runner), &env.config, &env.packages(&lua)).unwrap();
+
+    env.add_package(
+        "ripgrep.lua",
+        r#"return { name = "ripgrep", package_type = "cargo", version = "14.1.0" }"#,
+    );
+
+    let runner = RecordingRunner::new();
+    commands::install(&env.registry(&runner), &env.config, &env.packages(&lua)).unwrap();
+
+    assert_eq!(
+        runner.calls(),
+        [argv(&[
+            "cargo",
+            "install",
+            "ripgrep",
+            "--version",
+            "14.1.0"
+        ])]
+    );
+
+    let installed = env.installed();
+    assert_eq!(installed.len(), 1);
+    assert_eq!(installed[0].version.as_deref(), Some("14.1.0"));
+}
+
+#[test]
+fn install_reinstalls_over_packages_pipx_would_skip() {
+    let lua = Lua::new();
+    let mut env = Env::new();
+    env.add_package(
+        "black.lua",
+        r#"return { name = "black", package_type = "pipx" }"#,
+    );
+
+    let runner = RecordingRunner::new();
+    commands::install(&env.registry(&runner), &env.config, &env.packages(&lua)).unwrap();
+
+    env.add_package(
+        "black.lua",
+        r#"return { name = "black", package_type = "pipx", extras = { "d" } }"#,
+    );
+
+    let runner = RecordingRunner::new();
+    commands::install(&env.registry(&runner), &env.config, &env.packages(&lua)).unwrap();
+
+    assert_eq!(
+        runner.calls(),
+        [argv(&["pipx", "install", "--force", "black[d]"])]
+    );
+}
+
+#[test]
+fn sync_reinstalls_modified_packages_without_batching_them() {
+    let lua = Lua::new();
+    let mut env = Env::new();
+    env.add_package(
+        "gimp.lua",
+        r#"return { name = "org.gimp.GIMP", package_type = "flatpak" }"#,
+    );
+
+    let runner = RecordingRunner::new();
+    commands::install(&env.registry(&runner), &env.config, &env.packages(&lua)).unwrap();
+
+    env.add_package(
+        "gimp.lua",
+        r#"return { name = "org.gimp.GIMP", package_type = "flatpak", channel = "beta" }"#,
+    );
+
+    let runner = RecordingRunner::new();
+    commands::sync(&env.registry(&runner), &env.config, &env.packages(&lua)).unwrap();
+
+    assert_eq!(
+        runner.calls(),
+        [argv(&[
+            "flatpak",
+            "install",
+            "--reinstall",
+            "--system",
+            "-y",
+            "flathub",
+            "org.gimp.GIMP//beta"
+        ])]
+    );
+    assert_eq!(env.installed()[0].channel.as_deref(), Some("beta"));
+}
+
+#[test]
+fn purge_keeps_packages_whose_definition_changed() {
+    let lua = Lua::new();
+    let mut env = Env::new();
+    env.add_package("ripgrep.lua", RIPGREP);
+
+    let runner = RecordingRunner::new();
+    commands::install(&env.registry(&runner), &env.config, &env.packages(&lua)).unwrap();
+
+    env.add_package(
+        "ripgrep.lua",
+        r#"return { name = "ripgrep", package_type = "cargo", locked = true }"#,
+    );
+
+    let runner = RecordingRunner::new();
+    commands::purge(&env.registry(&runner), &env.config, &env.packages(&lua)).unwrap();
+
+    assert!(runner.calls().is_empty());
+    assert_eq!(env.installed().len(), 1);
+}
@@ -0,0 +1,274 @@
+#![allow(dead_code)]
+
+use std::fs::{create_dir_all, write};
+use std::path::PathBuf;
+use std::process::Command;
+
+use anyhow::Result;
+use mlua::Lua;
+use nexus::backend::{Backend, Registry};
+use nexus::config::Config;
+use nexus::package::{self, Package, PackageData, PackageType};
+use nexus::runner::{DryRunner, RecordingRunner, Runner};
+use nexus::state;
+use os_info::Type;
+use serde_json::Value;
+use tempfile::TempDir;
+
+/// A throwaway config dir with its own packages directory
+pub struct Env {
+    pub dir: TempDir,
+    pub config: Config,
+}
+
+impl Env {
+    pub fn new() -> Self {
+        let dir = TempDir::new().unwrap();
+        let config_dir = dir.path().join("config");
+        let data_dir = dir.path().join("data");
+
+        create_dir_all(config_dir.join("packages")).unwrap();
+        create_dir_all(&data_dir).unwrap();
+
+        Self {
+            config: Config {
+                packages: vec![],
+                backends: vec![],
+                config_dir,
+                data_dir,
+            },
+            dir,
+        }
+    }
+
+    /// Writes a package file and makes it part of the config
+    pub fn add_package(&mut self, file_name: &str, lua: &str) -> PathBuf {
+        let path = self.config.config_dir.join("packages").join(file_name);
+
+        write(&path, lua).unwrap();
+
+        if !self.config.packages.contains(&path) {
+            self.config.packages.push(path.clone());
+        }
+
+        path
+    }
+
+    /// Writes a backend file and makes it part of the config
+    pub fn add_backend(&mut self, file_name: &str, lua: &str) -> PathBuf {
+        let dir = self.config.config_dir.join("backends");
+        let path = dir.join(file_name);
+
+        create_dir_all(&dir).unwrap();
+        write(&path, lua).unwrap();
+        self.config.backends.push(path.clone());
+
+        path
+    }
+
+    pub fn packages(&self, lua: &Lua) -> Vec<Package> {
+        package::get_packages(lua, &self.config).unwrap()
+    }
+
+    pub fn installed(&self) -> Vec<PackageData> {
+        state::load(&self.config).unwrap()
+    }
+
+    pub fn registry(&self, runner: &RecordingRunner) -> Registry {
+        Registry::new(&self.config, Box::new(runner.clone()))
+    }
+
+    /// A registry whose builtin backends of `package_types` run on any os, so
+    /// the commands of e.g. apt can be checked on a mac
+    pub fn registry_on_any_os(
+        &self,
+        runner: &RecordingRunner,
+        package_types: &[PackageType],
+    ) -> Registry {
+        let mut registry = self.registry(runner);
+
+        for package_type in package_types {
+            registry.register(
+                package_type.clone(),
+                Box::new(AnyOs {
+                    builtin: Registry::new(&self.config, Box::new(DryRunner)),
+                    package_type: package_type.clone(),
+                }),
+            );
+        }
+
+        registry
+    }
+}
+
+/// A backend that runs `<name> <operation> <packages>` and is registered as
+/// `package_type = "<name>"`. Commands naming a package called "broken" fail.
+#[derive(Clone)]
+pub struct Fake {
+    name: String,
+    batch: bool,
+    installed_version: Option<String>,
+}
+
+impl Fake {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            batch: false,
+            installed_version: None,
+        }
+    }
+
+    /// Installs packages together with a single command
+    pub fn batching(mut self) -> Self {
+        self.batch = true;
+        self
+    }
+
+    /// Reports `version` as installed and installs exact versions as `name@version`
+    pub fn pinnable(mut self, version: &str) -> Self {
+        self.installed_version = Some(version.to_string());
+        self
+    }
+
+    pub fn register(self, registry: &mut Registry) {
+        registry.register(PackageType::Custom(self.name.clone()), Box::new(self));
+    }
+
+    fn run(&self, runner: &dyn Runner, args: &[&str]) -> Result<bool> {
+        let mut cmd = Command::new(&self.name);
+        cmd.args(args);
+
+        Ok(runner.run(cmd)? && !args.contains(&"broken"))
+    }
+}
+
+impl Backend for Fake {
+    fn is_supported(&self, _runner: &dyn Runner, _os: Type) -> bool {
+        true
+    }
+
+    fn install(&self, runner: &dyn Runner, pkg: &PackageData) -> Result<bool> {
+        self.install_batch(runner, &[pkg])
+    }
+
+    fn uninstall(&self, runner: &dyn Runner, pkg: &PackageData) -> Result<bool> {
+        self.run(runner, &["uninstall", &pkg.name])
+    }
+
+    fn update(&self, runner: &dyn Runner, pkg: &PackageData) -> Result<bool> {
+        self.run(runner, &["update", &pkg.name])
+    }
+
+    fn can_batch(&self, _pkg: &PackageData) -> bool {
+        self.batch
+    }
+
+    fn install_batch(&self, runner: &dyn Runner, pkgs: &[&PackageData]) -> Result<bool> {
+        let targets: Vec<String> = pkgs
+            .iter()
+            .map(|p| match &p.version {
+                Some(version) => format!("{}@{}", p.name, version),
+                None => p.name.clone(),
+            })
+            .collect();
+
+        let mut args = Vec::from(["install"]);
+        args.extend(targets.iter().map(|t| t.as_str()));
+
+        self.run(runner, &args)
+    }
+
+    fn can_pin(&self, _pkg: &PackageData) -> bool {
+        self.installed_version.is_some()
+    }
+
+    fn query(&self, _runner: &dyn Runner, _pkg: &PackageData) -> Result<Option<String>> {
+        Ok(self.installed_version.clone())
+    }
+}
+
+/// Hands everything but the os check to a builtin backend
+struct AnyOs {
+    builtin: Registry,
+    package_type: PackageType,
+}
+
+impl AnyOs {
+    fn backend(&self) -> &dyn Backend {
+        self.builtin.get(&self.package_type).unwrap()
+    }
+}
+
+impl Backend for AnyOs {
+    fn is_supported(&self, _runner: &dyn Runner, _os: Type) -> bool {
+        true
+    }
+
+    fn install(&self, runner: &dyn Runner, pkg: &PackageData) -> Result<bool> {
+        self.backend().install(runner, pkg)
+    }
+
+    fn uninstall(&self, runner: &dyn Runner, pkg: &PackageData) -> Result<bool> {
+        self.backend().uninstall(runner, pkg)
+    }
+
+    fn update(&self, runner: &dyn Runner, pkg: &PackageData) -> Result<bool> {
+        self.backend().update(runner, pkg)
+    }
+
+    fn install_package(&self, runner: &dyn Runner, pkg: &mut Package) -> Result<bool> {
+        self.backend().install_package(runner, pkg)
+    }
+
+    fn reinstall(&self, runner: &dyn Runner, pkg: &PackageData) -> Result<bool> {
+        self.backend().reinstall(runner, pkg)
+    }
+
+    fn reinstall_package(&self, runner: &dyn Runner, pkg: &mut Package) -> Result<bool> {
+        self.backend().reinstall_package(runner, pkg)
+    }
+
+    fn update_package(&self, runner: &dyn Runner, pkg: &mut Package) -> Result<bool> {
+        self.backend().update_package(runner, pkg)
+    }
+
+    fn can_batch(&self, pkg: &PackageData) -> bool {
+        self.backend().can_batch(pkg)
+    }
+
+    fn install_batch(&self, runner: &dyn Runner, pkgs: &[&PackageData]) -> Result<bool> {
+        self.backend().install_batch(runner, pkgs)
+    }
+
+    fn can_pin(&self, pkg: &PackageData) -> bool {
+        self.backend().can_pin(pkg)
+    }
+
+    fn requires_root(&self) -> bool {
+        self.backend().requires_root()
+    }
+
+    fn query(&self, runner: &dyn Runner, pkg: &PackageData) -> Result<Option<String>> {
+        self.backend().query(runner, pkg)
+    }
+}
+
+/// Builds package data from json, leaving every field not given at its default
+pub fn data(json: Value) -> PackageData {
+    let mut json = json;
+
+    for (key, default) in [
+        ("version", Value::Null),
+        ("channel", Value::Null),
+        ("hash", Value::String("hash".to_string())),
+    ] {
+        json.as_object_mut().unwrap().entry(key).or_insert(default);
+    }
+
+    serde_json::from_value(json).unwrap()
+}
+
+pub fn argv(args: &[&str]) -> Vec<String> {
+    args.iter().map(|a| a.to_string()).collect()
+}
@@ -0,0 +1,143 @@
+mod common;
+
+use std::fs::{read_to_string, write};
+
+use common::Env;
+use mlua::Lua;
+use nexus::backend::Registry;
+use nexus::commands;
+use nexus::history::{self, Operation};
+use nexus::runner::{DryRunner, RecordingRunner};
+
+#[test]
+fn install_appends_an_entry_per_package() {
+    let lua = Lua::new();
+    let mut env = Env::new();
+    env.add_package(
+        "ripgrep.lua",
+        r#"return {
+            name = "ripgrep",
+            package_type = "cargo",
+            post_install = function() end,
+        }"#,
+    );
+
+    let runner = RecordingRunner::new();
+    commands::install(&env.registry(&runner), &env.config, &env.packages(&lua)).unwrap();
+
+    let entries = history::load(&env.config).unwrap();
+    assert_eq!(entries.len(), 1);
+
+    let entry = &entries[0];
+    assert_eq!(entry.operation, Operation::Install);
+    assert_eq!(entry.name, "ripgrep");
+    assert_eq!(entry.package_type.to_string(), "cargo");
+    assert!(entry.success);
+    assert!(entry.timestamp > 0);
+    assert_eq!(entry.commands[0].command, "cargo install ripgrep");
+    assert_eq!(entry.commands[0].exit_code, Some(0));
+    assert_eq!(entry.hooks[0].hook, "postinstall");
+    assert!(entry.hooks[0].success);
+}
+
+#[test]
+fn failures_and_hook_errors_are_recorded() {
+    let lua = Lua::new();
+    let mut env = Env::new();
+    env.add_package(
+        "ripgrep.lua",
+        r#"return {
+            name = "ripgrep",
+            package_type = "cargo",
+            pre_install = function() error("no network") end,
+        }"#,
+    );
+
+    let runner = RecordingRunner::failing();
+    commands::install(&env.registry(&runner), &env.config, &env.packages(&lua)).unwrap();
+
+    let entry = history::load(&env.config).unwrap().remove(0);
+    assert!(!entry.success);
+    assert_eq!(entry.commands[0].exit_code, Some(1));
+    assert!(!entry.hooks[0].success);
+    assert!(entry.hooks[0]
+        .error
+        .as_ref()
+        .unwrap()
+        .contains("no network"));
+
+    // Later runs append instead of replacing the log
+    commands::install(&env.registry(&runner), &env.config, &env.packages(&lua)).unwrap();
+    assert_eq!(history::load(&env.config).unwrap().len(), 2);
+}
+
+#[test]
+fn dry_runs_leave_no_history() {
+    let lua = Lua::new();
+    let mut env = Env::new();
+    env.add_package(
+        "ripgrep.lua",
+        r#"return { name = "ripgrep", package_type = "cargo" }"#,
+    );
+
+    let registry = Registry::new(&env.config, Box::new(DryRunner));
+    commands::sync(&registry, &env.config, &env.packages(&lua)).unwrap();
+
+    assert!(!history::path(&env.config).exists());
+}
+
+#[test]
+fn truncated_lines_are_skipped() {
+    let lua = Lua::new();
+    let mut env = Env::new();
+    env.add_package(
+        "ripgrep.lua",
+        r#"return { name = "ripgrep", package_type = "cargo" }"#,
+    );
+
+    write(history::path(&env.config), "{\"timestamp\": 12\n").unwrap();
+
+    let runner = RecordingRunner::new();
+    commands::install(&env.registry(&runner), &env.config, &env.packages(&lua)).unwrap();
+
+    let entries = history::load(&env.config).unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].name, "ripgrep");
+    assert!(read_to_string(history::path(&env.config))
+        .unwrap()
+        .starts_with("{\"timestamp\": 12\n"));
+}
+
+#[test]
+fn dates_parse_to_the_start_of_the_day() {
+    assert_eq!(history::parse_date("1970-01-01"), Ok(0));
+    assert_eq!(history::parse_date("2024-02-29"), Ok(1709164800));
+    assert!(history::parse_date("2023-02-29").is_err());
+    assert!(history::parse_date("2024-13-01").is_err());
+    assert!(history::parse_date("yesterday").is_err());
+
+    assert_eq!(
+        history::format_timestamp(1709164800 + 3723),
+        "2024-02-29 01:02:03"
+    );
+}
+
+#[test]
+fn history_command_rejects_invalid_dates() {
+    let env = Env::new();
+
+    write(
+        history::path(&env.config),
+        r#"{"timestamp":0,"operation":"uninstall","name":"fd","package_type":"apt","commands":[],"hooks":[],"success":true,"duration_ms":5}"#,
+    )
+    .unwrap();
+
+    let args = |since: &str| nexus::cli::HistoryArgs {
+        package: Some("fd".to_string()),
+        since: Some(since.to_string()),
+        json: true,
+    };
+
+    assert!(commands::history(&env.config, &args("2024-01-01")).is_ok());
+    assert!(commands::history(&env.config, &args("01/01/2024")).is_err());
+}
@@ -0,0 +1,41 @@
+mod common;
+
+use std::fs::write;
+use std::process::Command;
+
+use common::Env;
+use nexus::lock::{self, Lock};
+
+#[test]
+fn lock_is_exclusive_and_names_its_holder() {
+    let env = Env::new();
+
+    let held = Lock::acquire(&env.config, false).unwrap();
+
+    let Err(e) = Lock::acquire(&env.config, false) else {
+        panic!("expected the lock to be held");
+    };
+    assert!(e.contains(&format!("PID {}", std::process::id())));
+
+    drop(held);
+
+    assert!(!lock::path(&env.config).exists());
+    assert!(Lock::acquire(&env.config, false).is_ok());
+}
+
+#[cfg(unix)]
+#[test]
+fn stale_locks_are_taken_over() {
+    let env = Env::new();
+
+    let mut child = Command::new("true").spawn().unwrap();
+    child.wait().unwrap();
+    write(lock::path(&env.config), child.id().to_string()).unwrap();
+
+    let _held = Lock::acquire(&env.config, false).unwrap();
+
+    assert_eq!(
+        std::fs::read_to_string(lock::path(&env.config)).unwrap(),
+        std::process::id().to_string()
+    );
+}
@@ -0,0 +1,123 @@
+mod common;
+
+use common::{data, Env};
+use mlua::Lua;
+use nexus::package::PackageType;
+use nexus::runner::RecordingRunner;
+use serde_json::json;
+
+/// Records every call along with how the optional fields looked from lua
+const BACKEND: &str = r#"
+calls = {}
+
+local function describe(op, pkg)
+    local target = pkg.name
+    if pkg.version then
+        target = target .. "@" .. pkg.version
+    end
+    table.insert(calls, op .. " " .. target .. " " .. type(pkg.channel))
+end
+
+nexus.register_backend("mine", {
+    install = function(pkg) describe("install", pkg) end,
+    uninstall = function(pkg) describe("uninstall", pkg) end,
+    update = function(pkg)
+        describe("update", pkg)
+        return pkg.name ~= "broken"
+    end,
+    is_installed = function(pkg)
+        describe("query", pkg)
+        return pkg.version or "1.0"
+    end,
+})
+"#;
+
+fn calls(lua: &Lua) -> Vec<String> {
+    lua.globals().get("calls").unwrap()
+}
+
+#[test]
+fn lua_backends_see_unset_fields_as_nil() {
+    let lua = Lua::new();
+    let mut env = Env::new();
+    env.add_backend("mine.lua", BACKEND);
+
+    let runner = RecordingRunner::new();
+    let mut registry = env.registry(&runner);
+    registry.load_lua_backends(&lua, &env.config).unwrap();
+
+    let backend = registry
+        .get(&PackageType::Custom("mine".to_string()))
+        .unwrap();
+    let pkg = data(json!({ "name": "tool", "package_type": "mine" }));
+
+    assert!(backend.install(registry.runner(), &pkg).unwrap());
+    assert!(backend.uninstall(registry.runner(), &pkg).unwrap());
+    assert!(backend.update(registry.runner(), &pkg).unwrap());
+    assert_eq!(
+        backend.query(registry.runner(), &pkg).unwrap(),
+        Some("1.0".to_string())
+    );
+
+    assert_eq!(
+        calls(&lua),
+        [
+            "install tool nil",
+            "uninstall tool nil",
+            "update tool nil",
+            "query tool nil"
+        ]
+    );
+    assert!(runner.calls().is_empty());
+}
+
+#[test]
+fn lua_backends_see_set_fields() {
+    let lua = Lua::new();
+    let mut env = Env::new();
+    env.add_backend("mine.lua", BACKEND);
+
+    let runner = RecordingRunner::new();
+    let mut registry = env.registry(&runner);
+    registry.load_lua_backends(&lua, &env.config).unwrap();
+
+    let backend = registry
+        .get(&PackageType::Custom("mine".to_string()))
+        .unwrap();
+    let pkg = data(json!({
+        "name": "tool",
+        "package_type": "mine",
+        "version": "2.0",
+        "channel": "beta",
+    }));
+
+    assert!(backend.install(registry.runner(), &pkg).unwrap());
+    assert_eq!(
+        backend.query(registry.runner(), &pkg).unwrap(),
+        Some("2.0".to_string())
+    );
+    assert_eq!(
+        calls(&lua),
+        ["install tool@2.0 string", "query tool@2.0 string"]
+    );
+
+    // Returning false is a failure
+    let broken = data(json!({ "name": "broken", "package_type": "mine" }));
+    assert!(!backend.update(registry.runner(), &broken).unwrap());
+}
+
+#[test]
+fn lua_backends_must_define_install_and_uninstall() {
+    let lua = Lua::new();
+    let mut env = Env::new();
+    env.add_backend(
+        "partial.lua",
+        r#"nexus.register_backend("partial", { install = function() end })"#,
+    );
+
+    let runner = RecordingRunner::new();
+    let mut registry = env.registry(&runner);
+    let err = registry.load_lua_backends(&lua, &env.config).unwrap_err();
+
+    assert!(err.contains("missing an uninstall function"), "{}", err);
+}
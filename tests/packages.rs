@@ -0,0 +1,212 @@
+mod common;
+
+use common::Env;
+use mlua::Lua;
+use nexus::package;
+
+fn cargo_package(name: &str, depends_on: &[&str]) -> String {
+    let deps: Vec<String> = depends_on.iter().map(|d| format!("\"{}\"", d)).collect();
+
+    format!(
+        r#"return {{ name = "{}", package_type = "cargo", depends_on = {{ {} }} }}"#,
+        name,
+        deps.join(", ")
+    )
+}
+
+#[test]
+fn packages_come_after_their_dependencies() {
+    let lua = Lua::new();
+    let mut env = Env::new();
+    env.add_package("a.lua", &cargo_package("a", &["c"]));
+    env.add_package("b.lua", &cargo_package("b", &[]));
+    env.add_package("c.lua", &cargo_package("c", &["b"]));
+
+    let names: Vec<String> = env
+        .packages(&lua)
+        .into_iter()
+        .map(|p| p.package_data.name)
+        .collect();
+
+    assert_eq!(names, ["b", "c", "a"]);
+}
+
+#[test]
+fn dependency_cycles_are_reported_with_their_files() {
+    let lua = Lua::new();
+    let mut env = Env::new();
+    let a = env.add_package("a.lua", &cargo_package("a", &["b"]));
+    let b = env.add_package("b.lua", &cargo_package("b", &["a"]));
+
+    let Err(e) = package::get_packages(&lua, &env.config) else {
+        panic!("expected a dependency cycle error");
+    };
+
+    assert_eq!(
+        e,
+        format!(
+            "Dependency cycle: a ({}) -> b ({}) -> a ({})",
+            a.display(),
+            b.display(),
+            a.display()
+        )
+    );
+}
+
+#[test]
+fn files_can_return_a_list_of_packages() {
+    let lua = Lua::new();
+    let mut env = Env::new();
+    env.add_package(
+        "tools.lua",
+        r#"return {
+            { name = "ripgrep", package_type = "cargo" },
+            { name = "fd-find", package_type = "cargo" },
+        }"#,
+    );
+
+    let names: Vec<String> = env
+        .packages(&lua)
+        .into_iter()
+        .map(|p| p.package_data.name)
+        .collect();
+
+    assert_eq!(names, ["ripgrep", "fd-find"]);
+}
+
+#[test]
+fn files_can_declare_packages_with_the_package_constructor() {
+    let lua = Lua::new();
+    let mut env = Env::new();
+    env.add_package(
+        "tools.lua",
+        r#"
+        package { name = "ripgrep", package_type = "cargo" }
+        return package { name = "fd-find", package_type = "cargo" }
+        "#,
+    );
+
+    let pkgs = env.packages(&lua);
+    let names: Vec<&str> = pkgs.iter().map(|p| p.package_data.name.as_str()).collect();
+
+    assert_eq!(names, ["ripgrep", "fd-find"]);
+    assert_ne!(pkgs[0].package_data.hash, pkgs[1].package_data.hash);
+}
+
+#[test]
+fn hash_ignores_formatting_and_comments() {
+    let lua = Lua::new();
+    let mut env = Env::new();
+    env.add_package(
+        "ripgrep.lua",
+        r#"return {
+    name = "ripgrep",
+    package_type = "cargo",
+    post_install = function()
+        print("hi")
+    end,
+}"#,
+    );
+    let before = env.packages(&lua).remove(0).package_data;
+
+    env.add_package(
+        "ripgrep.lua",
+        r#"-- Search tool
+return {
+  name = "ripgrep", package_type = "cargo",
+
+  post_install = function()
+    -- say hi
+    print("hi")  --[[ twice? ]]
+  end,
+}"#,
+    );
+    let after = env.packages(&lua).remove(0).package_data;
+
+    assert_eq!(before.hash, after.hash);
+    assert_ne!(before.file_hash, after.file_hash);
+}
+
+#[test]
+fn hash_changes_with_fields_and_hooks() {
+    let lua = Lua::new();
+    let mut env = Env::new();
+    let mut hash = |lua_src: &str| {
+        env.add_package("ripgrep.lua", lua_src);
+        env.packages(&lua).remove(0).package_data.hash
+    };
+
+    let plain = hash(r#"return { name = "ripgrep", package_type = "cargo" }"#);
+    let locked = hash(r#"return { name = "ripgrep", package_type = "cargo", locked = true }"#);
+    let hook = hash(
+        r#"return { name = "ripgrep", package_type = "cargo", post_install = function() print("hi") end }"#,
+    );
+    let other_hook = hash(
+        r#"return { name = "ripgrep", package_type = "cargo", post_install = function() print("bye") end }"#,
+    );
+
+    assert_ne!(plain, locked);
+    assert_ne!(plain, hook);
+    assert_ne!(hook, other_hook);
+}
+
+#[test]
+fn packages_must_be_unique_per_backend() {
+    let lua = Lua::new();
+    let mut env = Env::new();
+    let a = env.add_package("a.lua", &cargo_package("ripgrep", &[]));
+    let b = env.add_package("b.lua", &cargo_package("ripgrep", &[]));
+
+    let Err(e) = package::get_packages(&lua, &env.config) else {
+        panic!("expected a duplicate package error");
+    };
+
+    assert_eq!(
+        e,
+        format!(
+            "ripgrep [cargo] is declared in both {} and {}",
+            a.display(),
+            b.display()
+        )
+    );
+}
+
+#[test]
+fn git_steps_accept_commands_but_uninstall_no_functions() {
+    let lua = Lua::new();
+    let mut env = Env::new();
+    env.add_package(
+        "tool.lua",
+        r#"return {
+            name = "tool",
+            package_type = "git",
+            repo = "https://example.com/tool.git",
+            build = function(dir) end,
+            install = "make install",
+            uninstall = { "make uninstall", "rm -f ~/.toolrc" },
+        }"#,
+    );
+
+    let data = env.packages(&lua).remove(0).package_data;
+    assert_eq!(data.install_commands, ["make install"]);
+    assert_eq!(
+        data.uninstall_commands,
+        ["make uninstall", "rm -f ~/.toolrc"]
+    );
+
+    env.add_package(
+        "tool.lua",
+        r#"return {
+            name = "tool",
+            package_type = "git",
+            repo = "https://example.com/tool.git",
+            uninstall = function(dir) end,
+        }"#,
+    );
+
+    let Err(e) = package::get_packages(&lua, &env.config) else {
+        panic!("expected uninstall functions to be rejected");
+    };
+
+    assert!(e.contains("The uninstall step of tool has to be a list of commands"));
+}
@@ -0,0 +1,267 @@
+mod common;
+
+use common::{argv, Env, Fake};
+use mlua::Lua;
+use nexus::backend::Registry;
+use nexus::cli::RollbackArgs;
+use nexus::commands;
+use nexus::package::PackageType;
+use nexus::runner::{DryRunner, RecordingRunner};
+use nexus::state;
+
+fn to(generation: Option<u64>) -> RollbackArgs {
+    RollbackArgs {
+        generation,
+        list: false,
+    }
+}
+
+#[test]
+fn each_run_that_changes_state_is_a_generation() {
+    let lua = Lua::new();
+    let mut env = Env::new();
+    let runner = RecordingRunner::new();
+
+    env.add_package(
+        "ripgrep.lua",
+        r#"return { name = "ripgrep", package_type = "cargo" }"#,
+    );
+    commands::install(&env.registry(&runner), &env.config, &env.packages(&lua)).unwrap();
+
+    env.add_package(
+        "fd.lua",
+        r#"return { name = "fd-find", package_type = "cargo" }"#,
+    );
+    commands::install(&env.registry(&runner), &env.config, &env.packages(&lua)).unwrap();
+
+    // Nothing left to install, so nothing changes
+    commands::install(&env.registry(&runner), &env.config, &env.packages(&lua)).unwrap();
+
+    assert_eq!(state::generations(&env.config).unwrap(), [1, 2]);
+    assert_eq!(state::load_generation(&env.config, 1).unwrap().len(), 1);
+    assert_eq!(state::load_generation(&env.config, 2).unwrap().len(), 2);
+}
+
+#[test]
+fn rollback_restores_the_previous_generation() {
+    let lua = Lua::new();
+    let mut env = Env::new();
+    let runner = RecordingRunner::new();
+
+    env.add_package(
+        "ripgrep.lua",
+        r#"return { name = "ripgrep", package_type = "cargo" }"#,
+    );
+    commands::install(&env.registry(&runner), &env.config, &env.packages(&lua)).unwrap();
+
+    let rollback = commands::rollback(
+        &env.registry(&runner),
+        &env.config,
+        &env.packages(&lua),
+        &to(None),
+    );
+    assert!(rollback.is_err());
+
+    env.add_package(
+        "fd.lua",
+        r#"return { name = "fd-find", package_type = "cargo" }"#,
+    );
+    commands::install(&env.registry(&runner), &env.config, &env.packages(&lua)).unwrap();
+
+    let runner = RecordingRunner::new();
+    commands::rollback(
+        &env.registry(&runner),
+        &env.config,
+        &env.packages(&lua),
+        &to(None),
+    )
+    .unwrap();
+
+    assert_eq!(runner.calls(), [argv(&["cargo", "uninstall", "fd-find"])]);
+    assert_eq!(env.installed().len(), 1);
+    assert_eq!(env.installed()[0].name, "ripgrep");
+
+    // Rolling back is a run of its own, so it can be undone
+    assert_eq!(state::generations(&env.config).unwrap(), [1, 2, 3]);
+
+    let runner = RecordingRunner::new();
+    commands::rollback(
+        &env.registry(&runner),
+        &env.config,
+        &env.packages(&lua),
+        &to(Some(2)),
+    )
+    .unwrap();
+
+    assert_eq!(runner.calls(), [argv(&["cargo", "install", "fd-find"])]);
+    assert_eq!(env.installed().len(), 2);
+}
+
+#[test]
+fn dry_run_rollback_changes_nothing() {
+    let lua = Lua::new();
+    let mut env = Env::new();
+    let runner = RecordingRunner::new();
+
+    env.add_package(
+        "ripgrep.lua",
+        r#"return { name = "ripgrep", package_type = "cargo" }"#,
+    );
+    commands::install(&env.registry(&runner), &env.config, &env.packages(&lua)).unwrap();
+    commands::purge(&env.registry(&runner), &env.config, &[]).unwrap();
+
+    let registry = Registry::new(&env.config, Box::new(DryRunner));
+    commands::rollback(&registry, &env.config, &env.packages(&lua), &to(Some(1))).unwrap();
+
+    assert!(env.installed().is_empty());
+    assert_eq!(state::generations(&env.config).unwrap(), [1, 2]);
+}
+
+#[test]
+fn rollback_pins_the_recorded_version() {
+    let lua = Lua::new();
+    let mut env = Env::new();
+    env.add_package(
+        "a.lua",
+        r#"return { name = "a", package_type = "pinnable" }"#,
+    );
+    let pkgs = env.packages(&lua);
+
+    let registry = |runner: &RecordingRunner| {
+        let mut registry = env.registry(runner);
+        Fake::new("pinnable")
+            .pinnable("1.0")
+            .register(&mut registry);
+        registry
+    };
+
+    let runner = RecordingRunner::new();
+    commands::install(&registry(&runner), &env.config, &pkgs).unwrap();
+    commands::purge(&registry(&runner), &env.config, &[]).unwrap();
+
+    let runner = RecordingRunner::new();
+    commands::rollback(&registry(&runner), &env.config, &pkgs, &to(Some(1))).unwrap();
+
+    assert_eq!(runner.calls(), [argv(&["pinnable", "install", "a@1.0"])]);
+
+    // Only the reinstall was pinned, not the package itself
+    let installed = env.installed().remove(0);
+    assert_eq!(installed.version, None);
+    assert_eq!(
+        installed.installed.unwrap().version,
+        Some("1.0".to_string())
+    );
+}
+
+#[test]
+fn apt_rollback_allows_downgrading_to_the_recorded_version() {
+    let lua = Lua::new();
+    let mut env = Env::new();
+    env.add_package(
+        "curl.lua",
+        r#"return { name = "curl", package_type = "apt" }"#,
+    );
+    let pkgs = env.packages(&lua);
+    let registry = |runner: &RecordingRunner| env.registry_on_any_os(runner, &[PackageType::Apt]);
+
+    let runner = RecordingRunner::new();
+    runner.set_output("dpkg-query", "7.88.1");
+    commands::install(&registry(&runner), &env.config, &pkgs).unwrap();
+
+    let runner = RecordingRunner::new();
+    runner.set_output("dpkg-query", "8.5.0");
+    commands::update(&registry(&runner), &env.config, &pkgs).unwrap();
+
+    let runner = RecordingRunner::new();
+    runner.set_output("dpkg-query", "7.88.1");
+    commands::rollback(&registry(&runner), &env.config, &pkgs, &to(Some(1))).unwrap();
+
+    assert_eq!(
+        runner.calls(),
+        [argv(&[
+            "sudo",
+            "apt",
+            "install",
+            "--allow-downgrades",
+            "curl=7.88.1",
+            "-y"
+        ])]
+    );
+}
+
+#[test]
+fn snap_rollback_refreshes_to_the_recorded_channel() {
+    let lua = Lua::new();
+    let mut env = Env::new();
+
+    env.add_package(
+        "code.lua",
+        r#"return { name = "code", package_type = "snap" }"#,
+    );
+    let runner = RecordingRunner::new();
+    commands::install(
+        &env.registry_on_any_os(&runner, &[PackageType::Snap]),
+        &env.config,
+        &env.packages(&lua),
+    )
+    .unwrap();
+
+    env.add_package(
+        "code.lua",
+        r#"return { name = "code", package_type = "snap", channel = "beta" }"#,
+    );
+    let runner = RecordingRunner::new();
+    commands::install(
+        &env.registry_on_any_os(&runner, &[PackageType::Snap]),
+        &env.config,
+        &env.packages(&lua),
+    )
+    .unwrap();
+
+    let runner = RecordingRunner::new();
+    commands::rollback(
+        &env.registry_on_any_os(&runner, &[PackageType::Snap]),
+        &env.config,
+        &env.packages(&lua),
+        &to(Some(1)),
+    )
+    .unwrap();
+
+    assert_eq!(
+        runner.calls(),
+        [argv(&[
+            "sudo",
+            "snap",
+            "refresh",
+            "code",
+            "--channel=stable"
+        ])]
+    );
+    assert_eq!(env.installed()[0].channel, None);
+}
+
+#[test]
+fn brew_rollback_leaves_the_installed_version() {
+    let lua = Lua::new();
+    let mut env = Env::new();
+    env.add_package(
+        "python.lua",
+        r#"return { name = "python", package_type = "brew" }"#,
+    );
+    let pkgs = env.packages(&lua);
+    let registry = |runner: &RecordingRunner| env.registry_on_any_os(runner, &[PackageType::Brew]);
+
+    let runner = RecordingRunner::new();
+    runner.set_output("brew", "python 3.12.1_1");
+    commands::install(&registry(&runner), &env.config, &pkgs).unwrap();
+
+    let runner = RecordingRunner::new();
+    runner.set_output("brew", "python 3.12.1_1 3.12.2");
+    commands::update(&registry(&runner), &env.config, &pkgs).unwrap();
+
+    // brew can't install 3.12.1_1 again, so there's nothing to run
+    let runner = RecordingRunner::new();
+    commands::rollback(&registry(&runner), &env.config, &pkgs, &to(Some(1))).unwrap();
+
+    assert!(runner.calls().is_empty());
+}
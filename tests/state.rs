@@ -0,0 +1,122 @@
+mod common;
+
+use std::fs::{read_to_string, write};
+
+use common::{argv, data, Env};
+use mlua::Lua;
+use nexus::commands;
+use nexus::runner::RecordingRunner;
+use nexus::state;
+use serde_json::json;
+
+#[test]
+fn missing_state_is_empty() {
+    let env = Env::new();
+
+    assert!(state::load(&env.config).unwrap().is_empty());
+}
+
+#[test]
+fn save_keeps_a_backup_of_the_previous_state() {
+    let env = Env::new();
+    let ripgrep = data(json!({ "name": "ripgrep", "package_type": "cargo" }));
+    let fd = data(json!({ "name": "fd-find", "package_type": "cargo" }));
+
+    state::save(&env.config, std::slice::from_ref(&ripgrep)).unwrap();
+    let first = read_to_string(state::path(&env.config)).unwrap();
+
+    state::save(&env.config, &[ripgrep, fd]).unwrap();
+
+    assert_eq!(
+        read_to_string(state::backup_path(&env.config)).unwrap(),
+        first
+    );
+    assert_eq!(state::load(&env.config).unwrap().len(), 2);
+    assert!(!env
+        .config
+        .config_dir
+        .join("installed_packages.json.tmp")
+        .exists());
+}
+
+#[test]
+fn corrupted_state_is_an_error_instead_of_empty() {
+    let lua = Lua::new();
+    let mut env = Env::new();
+    env.add_package(
+        "ripgrep.lua",
+        r#"return { name = "ripgrep", package_type = "cargo" }"#,
+    );
+
+    write(state::path(&env.config), "[{ \"name\": ").unwrap();
+
+    assert!(state::load(&env.config).is_err());
+
+    let runner = RecordingRunner::new();
+    let result = commands::install(&env.registry(&runner), &env.config, &env.packages(&lua));
+
+    assert!(result.is_err());
+    assert!(runner.calls().is_empty());
+    assert_eq!(
+        read_to_string(state::path(&env.config)).unwrap(),
+        "[{ \"name\": "
+    );
+}
+
+#[test]
+fn bare_lists_are_migrated_to_the_versioned_state() {
+    let env = Env::new();
+
+    write(
+        state::path(&env.config),
+        r#"[{ "name": "ripgrep", "package_type": "cargo", "version": null, "channel": null, "hash": "abc" }]"#,
+    )
+    .unwrap();
+
+    let installed = state::load(&env.config).unwrap();
+    assert_eq!(installed.len(), 1);
+    assert_eq!(installed[0].name, "ripgrep");
+
+    state::save(&env.config, &installed).unwrap();
+
+    let saved: serde_json::Value =
+        serde_json::from_str(&read_to_string(state::path(&env.config)).unwrap()).unwrap();
+    assert_eq!(saved["schema"], json!(state::SCHEMA));
+    assert_eq!(saved["packages"][0]["name"], json!("ripgrep"));
+}
+
+#[test]
+fn newer_schemas_are_refused() {
+    let env = Env::new();
+
+    write(
+        state::path(&env.config),
+        json!({ "schema": state::SCHEMA + 1, "packages": [] }).to_string(),
+    )
+    .unwrap();
+
+    assert!(state::load(&env.config).is_err());
+}
+
+#[test]
+fn install_records_metadata() {
+    let lua = Lua::new();
+    let mut env = Env::new();
+    let path = env.add_package(
+        "ripgrep.lua",
+        r#"return { name = "ripgrep", package_type = "cargo" }"#,
+    );
+
+    let runner = RecordingRunner::new();
+    runner.set_output("cargo", "ripgrep v14.1.0:\n    rg");
+    commands::install(&env.registry(&runner), &env.config, &env.packages(&lua)).unwrap();
+
+    let installed = env.installed().remove(0);
+    let info = installed.installed.unwrap();
+
+    assert_eq!(installed.file, Some(path.display().to_string()));
+    assert_eq!(info.nexus_version, env!("CARGO_PKG_VERSION"));
+    assert_eq!(info.version.as_deref(), Some("14.1.0"));
+    assert!(info.installed_at > 0);
+    assert_eq!(runner.queries(), [argv(&["cargo", "install", "--list"])]);
+}